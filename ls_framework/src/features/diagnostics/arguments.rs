@@ -0,0 +1,350 @@
+use std::sync::{Arc, Mutex};
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use crate::language_def::{self, Calls, LanguageDefinition};
+use crate::metadata::{
+    is_optional, resolve_call, AstQuery, Call, Direction, Signature, SymbolTableQuery, Type,
+    TypeChecker, VisitNode, Visitable,
+};
+use crate::settings::Settings;
+
+use super::provider::DiagnosticProvider;
+use super::utils::{count, new_diagnostic};
+
+const SOURCE: &str = "Call";
+
+pub struct Arguments {}
+
+impl DiagnosticProvider for Arguments {
+    fn get_diagnostics(
+        ast_query: &Arc<Mutex<impl AstQuery>>,
+        symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        _settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let Some(config) = LanguageDefinition::get().calls.as_ref() else {
+            return vec![];
+        };
+
+        let ast_query = ast_query.lock().unwrap();
+        let symbol_table_query = symbol_table_query.lock().unwrap();
+        let root = ast_query.visit_root();
+
+        let checker = ArgumentChecker { config };
+        let mut type_checker = TypeChecker::new(&*symbol_table_query);
+
+        let mut diagnostics = vec![];
+        for node in root.get_descendants() {
+            let Some(call) = resolve_call(&*symbol_table_query, config, &node) else {
+                continue;
+            };
+            diagnostics.append(&mut checker.check(&node, &call, type_checker.as_mut()));
+        }
+
+        diagnostics
+    }
+}
+
+struct ArgumentChecker<'a> {
+    config: &'a Calls,
+}
+
+impl ArgumentChecker<'_> {
+    /// Reports the arguments of a call not matching the parameters of its callable.
+    fn check(
+        &self,
+        node: &VisitNode,
+        call: &Call,
+        type_checker: Option<&mut TypeChecker<impl SymbolTableQuery>>,
+    ) -> Vec<Diagnostic> {
+        // Unknown callable, or a type without constructor
+        if call.signatures.is_empty() {
+            return vec![];
+        }
+
+        let Some(signature) = call.get_signature().or_else(|| {
+            call.signatures
+                .iter()
+                .find(|signature| self.accepts_count(signature, call.arguments.len()))
+        }) else {
+            let expected: Vec<String> = call
+                .signatures
+                .iter()
+                .map(|signature| format!("`{}`", signature))
+                .collect();
+            return vec![new_diagnostic(
+                SOURCE,
+                call.range,
+                DiagnosticSeverity::ERROR,
+                "argument-count",
+                format!(
+                    "No overload of `{}` takes {}, expected one of {}.",
+                    call.signatures[0].callable.get_name(),
+                    count(call.arguments.len(), "argument"),
+                    expected.join(", ")
+                ),
+            )];
+        };
+
+        let mut diagnostics = vec![];
+        let names_checked = self.check_names(call, signature, &mut diagnostics);
+        if names_checked {
+            self.check_count(node, call, signature, &mut diagnostics);
+        }
+        if let Some(type_checker) = type_checker {
+            self.check_types(node, call, signature, type_checker, &mut diagnostics);
+        }
+
+        diagnostics
+    }
+
+    /// Reports the named arguments not naming a parameter, returning whether all did.
+    fn check_names(
+        &self,
+        call: &Call,
+        signature: &Signature,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> bool {
+        let mut valid = true;
+        for argument in &call.arguments {
+            let Some(name) = &argument.name else {
+                continue;
+            };
+            if signature
+                .parameters
+                .iter()
+                .any(|parameter| parameter.get_name() == *name)
+            {
+                continue;
+            }
+
+            valid = false;
+            diagnostics.push(new_diagnostic(
+                SOURCE,
+                argument.range,
+                DiagnosticSeverity::ERROR,
+                "argument-name",
+                format!(
+                    "`{}` has no parameter named `{}`, expected `{}`.",
+                    signature.callable.get_name(),
+                    name,
+                    signature
+                ),
+            ));
+        }
+
+        valid
+    }
+
+    /// Reports the calls given too many arguments, or not enough for the parameters
+    /// which aren't optional.
+    fn check_count(
+        &self,
+        node: &VisitNode,
+        call: &Call,
+        signature: &Signature,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let given = call.arguments.len();
+        let total = signature.parameters.len();
+
+        if call
+            .arguments
+            .iter()
+            .any(|argument| argument.name.is_some())
+        {
+            // Named arguments can be given in any order, the missing ones are reported by name
+            let missing: Vec<String> = signature
+                .parameters
+                .iter()
+                .enumerate()
+                .filter(|(index, parameter)| {
+                    !is_optional(self.config, parameter)
+                        && !call.arguments.iter().enumerate().any(|(i, argument)| {
+                            match &argument.name {
+                                Some(name) => *name == parameter.get_name(),
+                                None => i == *index,
+                            }
+                        })
+                })
+                .map(|(_, parameter)| format!("`{}`", parameter.get_name()))
+                .collect();
+            if !missing.is_empty() {
+                diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    node.get().range,
+                    DiagnosticSeverity::ERROR,
+                    "argument-count",
+                    format!(
+                        "Missing arguments for {}, expected `{}`.",
+                        missing.join(", "),
+                        signature
+                    ),
+                ));
+            }
+            return;
+        }
+
+        if self.accepts_count(signature, given) || self.is_reported_action(signature, given) {
+            return;
+        }
+
+        let required = self.count_required(signature);
+        let expected = if required == total {
+            count(total, "argument")
+        } else if given < required {
+            format!("at least {}", count(required, "argument"))
+        } else {
+            format!("at most {}", count(total, "argument"))
+        };
+        diagnostics.push(new_diagnostic(
+            SOURCE,
+            node.get().range,
+            DiagnosticSeverity::ERROR,
+            "argument-count",
+            format!(
+                "`{}` is given {} but expects {}: `{}`.",
+                signature.callable.get_name(),
+                count(given, "argument"),
+                expected,
+                signature
+            ),
+        ));
+    }
+
+    /// Reports the arguments whose type doesn't match the one of their parameter.
+    fn check_types(
+        &self,
+        node: &VisitNode,
+        call: &Call,
+        signature: &Signature,
+        type_checker: &mut TypeChecker<impl SymbolTableQuery>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let descendants = node.get_descendants();
+        for (index, argument) in call.arguments.iter().enumerate() {
+            let Some(parameter) = signature.get_parameter(index, argument) else {
+                continue;
+            };
+            let Some(expression) = descendants.iter().find(|descendant| {
+                descendant.get().range == argument.range
+                    && descendant.get().symbol == language_def::Symbol::Expression
+            }) else {
+                continue;
+            };
+            let Some(parameter_type) = signature.get_parameter_type(parameter) else {
+                continue;
+            };
+
+            let parameter_type = type_checker.resolve_type(&parameter_type, call.range.start);
+            let argument_type = type_checker.infer(*expression);
+            if argument_type == Type::Unknown || parameter_type == Type::Unknown {
+                continue;
+            }
+
+            // Written arguments receive the value of the parameter
+            let valid = match parameter.get_direction() {
+                Some(Direction::Out) => argument_type.accepts(&parameter_type),
+                Some(Direction::InOut) => argument_type == parameter_type,
+                _ => parameter_type.accepts(&argument_type),
+            };
+            if !valid {
+                diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    argument.range,
+                    DiagnosticSeverity::ERROR,
+                    "argument-type",
+                    format!(
+                        "`{}` of type `{}` can't be passed to the parameter `{}` of type `{}`, expected `{}`.",
+                        argument.text,
+                        argument_type,
+                        parameter.get_name(),
+                        parameter_type,
+                        signature
+                    ),
+                ));
+            }
+        }
+    }
+
+    fn accepts_count(&self, signature: &Signature, given: usize) -> bool {
+        self.count_required(signature) <= given && given <= signature.parameters.len()
+    }
+
+    fn count_required(&self, signature: &Signature) -> usize {
+        signature
+            .parameters
+            .iter()
+            .filter(|parameter| !is_optional(self.config, parameter))
+            .count()
+    }
+
+    /// Whether the call is an action given only its directional parameters, which
+    /// the directions check reports.
+    fn is_reported_action(&self, signature: &Signature, given: usize) -> bool {
+        let is_action = LanguageDefinition::get()
+            .directions
+            .as_ref()
+            .is_some_and(|directions| directions.actions.contains(&signature.callable.get_kind()));
+        let directional = signature
+            .parameters
+            .iter()
+            .filter(|parameter| parameter.get_direction().is_some())
+            .count();
+
+        is_action && given == directional && directional < signature.parameters.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arguments;
+    use crate::features::diagnostics::provider::tests::get_messages;
+    use crate::metadata::Imports;
+
+    #[test]
+    fn test_arguments() {
+        let source = "extern register<T> {
+    register(bit<32> size);
+    void read(out T result, in bit<32> index);
+    void write(in bit<32> index, in T value);
+}
+
+extern void log(in bit<8> value, @optional in bit<8> level);
+
+control C(inout bit<32> x, in bool b) {
+    register<bit<32>>(1024) r;
+    register<bit<32>>() s;
+
+    action set(in bit<8> v) { }
+
+    apply {
+        r.read(x, 0);
+        r.read(x);
+        r.write(0, b);
+        r.write(index = 0, value = x);
+        r.write(index = 0, data = x);
+        set(1, 2);
+        set(b);
+        log(1);
+        log(1, 2, 3);
+    }
+}
+";
+        let mut messages = get_messages::<Arguments>(source, &Imports::default());
+        messages.sort();
+
+        assert_eq!(
+            messages,
+            vec![
+                "`b` of type `bool` can't be passed to the parameter `v` of type `bit<8>`, expected `set(in bit<8> v)`.",
+                "`b` of type `bool` can't be passed to the parameter `value` of type `bit<32>`, expected `write(in bit<32> index, in bit<32> value)`.",
+                "`log` is given 3 arguments but expects at most 2 arguments: `log(in bit<8> value, in bit<8> level)`.",
+                "`read` is given 1 argument but expects 2 arguments: `read(out bit<32> result, in bit<32> index)`.",
+                "`register` is given 0 arguments but expects 1 argument: `register(bit<32> size)`.",
+                "`set` is given 2 arguments but expects 1 argument: `set(in bit<8> v)`.",
+                "`write` has no parameter named `data`, expected `write(in bit<32> index, in bit<32> value)`.",
+            ]
+        );
+    }
+}
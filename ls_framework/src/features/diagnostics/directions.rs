@@ -0,0 +1,594 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+use crate::language_def::{self, Calls, LanguageDefinition};
+use crate::metadata::{
+    get_parameters, resolve_call, AstQuery, Direction, NodeKind, Symbol, SymbolTableQuery,
+    VisitNode, Visitable,
+};
+use crate::settings::Settings;
+use crate::utils::{compact, is_dotted_identifier};
+
+use super::provider::DiagnosticProvider;
+use super::utils::{count, count_arguments, is_rule, new_diagnostic};
+
+const SOURCE: &str = "Direction";
+
+pub struct Directions {}
+
+impl DiagnosticProvider for Directions {
+    fn get_diagnostics(
+        ast_query: &Arc<Mutex<impl AstQuery>>,
+        symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        _settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let definition = LanguageDefinition::get();
+        let (Some(calls), Some(config)) = (&definition.calls, &definition.directions) else {
+            return vec![];
+        };
+
+        let ast_query = ast_query.lock().unwrap();
+        let symbol_table_query = symbol_table_query.lock().unwrap();
+        let root = ast_query.visit_root();
+
+        let checker = DirectionChecker {
+            calls,
+            config,
+            symbol_table: &*symbol_table_query,
+        };
+
+        let mut diagnostics = vec![];
+        for node in root.get_descendants() {
+            diagnostics.extend(checker.check_assignment(&node));
+            diagnostics.extend(checker.check_call(&node));
+            diagnostics.extend(checker.check_action_parameters(&node));
+            diagnostics.append(&mut checker.check_body(&node));
+        }
+        diagnostics.append(&mut checker.check_table_actions(root));
+
+        diagnostics
+    }
+}
+
+struct DirectionChecker<'a, Q: SymbolTableQuery> {
+    calls: &'a Calls,
+    config: &'a language_def::Directions,
+    symbol_table: &'a Q,
+}
+
+/// State of the walk through a body, looking for `out` parameters read before being assigned.
+struct Flow {
+    out_parameters: Vec<Symbol>,
+    reported: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<Q: SymbolTableQuery> DirectionChecker<'_, Q> {
+    /// Reports the assignments to read-only parameters.
+    fn check_assignment(&self, node: &VisitNode) -> Option<Diagnostic> {
+        let (target, range) = self.get_assignment_target(node)?;
+        let description = self.describe_read_only(&target, range.start)?;
+
+        let head = get_head(&target);
+        let message = if head == target {
+            format!("Cannot assign to `{}`, it is {}.", target, description)
+        } else {
+            format!(
+                "Cannot assign to `{}`, `{}` is {}.",
+                target, head, description
+            )
+        };
+
+        Some(new_diagnostic(
+            SOURCE,
+            range,
+            DiagnosticSeverity::ERROR,
+            "read-only",
+            message,
+        ))
+    }
+
+    /// Reports the arguments written by the callable that can't be, and the actions
+    /// called without their directionless parameters.
+    fn check_call(&self, node: &VisitNode) -> Vec<Diagnostic> {
+        let Some(call) = resolve_call(self.symbol_table, self.calls, node) else {
+            return vec![];
+        };
+        let Some(signature) = call.get_signature() else {
+            return vec![];
+        };
+        let (callee, parameters) = (&signature.callable, &signature.parameters);
+        let mut diagnostics = vec![];
+
+        for (index, argument) in call.arguments.iter().enumerate() {
+            let Some(parameter) = signature.get_parameter(index, argument) else {
+                continue;
+            };
+            let (text, range) = (&argument.text, &argument.range);
+            let Some(direction @ (Direction::Out | Direction::InOut)) = parameter.get_direction()
+            else {
+                continue;
+            };
+            // Don't care, the written value is discarded
+            if text == "_" {
+                continue;
+            }
+
+            let reason = if !is_location(text) {
+                "it isn't a writable location".to_string()
+            } else if let Some(description) = self.describe_read_only(text, range.start) {
+                format!("`{}` is {}", get_head(text), description)
+            } else {
+                continue;
+            };
+            diagnostics.push(new_diagnostic(
+                SOURCE,
+                *range,
+                DiagnosticSeverity::ERROR,
+                "read-only",
+                format!(
+                    "`{}` can't be passed to the `{}` parameter `{}`, {}.",
+                    text,
+                    direction,
+                    parameter.get_name(),
+                    reason
+                ),
+            ));
+        }
+
+        let directional = parameters
+            .iter()
+            .filter(|parameter| parameter.get_direction().is_some())
+            .count();
+        if self.is_action(callee)
+            && call.arguments.len() == directional
+            && directional < parameters.len()
+        {
+            diagnostics.push(new_diagnostic(
+                SOURCE,
+                node.get().range,
+                DiagnosticSeverity::ERROR,
+                "action-arguments",
+                format!(
+                    "`{}` is given {} but has {}, its directionless parameters are only bound by the control plane when a table runs it.",
+                    callee.get_name(),
+                    count(call.arguments.len(), "argument"),
+                    count(parameters.len(), "parameter")
+                ),
+            ));
+        }
+
+        diagnostics
+    }
+
+    /// Reports the directional parameters of an action following directionless ones.
+    fn check_action_parameters(&self, node: &VisitNode) -> Vec<Diagnostic> {
+        let Some(action) = self
+            .get_declared_symbol(node)
+            .filter(|symbol| self.is_action(symbol))
+        else {
+            return vec![];
+        };
+
+        let mut directionless = false;
+        let mut diagnostics = vec![];
+        for parameter in get_parameters(self.symbol_table, self.calls, action) {
+            if parameter.get_direction().is_none() {
+                directionless = true;
+            } else if directionless {
+                diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    parameter.get_definition_range(),
+                    DiagnosticSeverity::ERROR,
+                    "parameter-order",
+                    format!(
+                        "Directional parameter `{}` must come before the directionless ones, bound by the control plane.",
+                        parameter.get_name()
+                    ),
+                ));
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Reports the actions of a table not given exactly their directional parameters,
+    /// the others being bound by the control plane.
+    fn check_table_actions(&self, root: VisitNode) -> Vec<Diagnostic> {
+        let Some(tables) = LanguageDefinition::get().tables.as_ref() else {
+            return vec![];
+        };
+
+        let mut diagnostics = vec![];
+        for list in root.get_descendants() {
+            if !is_rule(&list, &tables.actions) {
+                continue;
+            }
+
+            for action in list.get_descendants() {
+                if !is_rule(&action, &tables.action) {
+                    continue;
+                }
+                let Some(symbol) = action
+                    .get_descendants()
+                    .iter()
+                    .find_map(|node| self.get_linked_symbol(node))
+                    .filter(|symbol| self.is_action(symbol))
+                else {
+                    continue;
+                };
+
+                let directional = get_parameters(self.symbol_table, self.calls, symbol)
+                    .iter()
+                    .filter(|parameter| parameter.get_direction().is_some())
+                    .count();
+                let content = &action.get().content;
+                let given = content
+                    .find('(')
+                    .map_or(0, |i| count_arguments(&content[i..]));
+
+                if given != directional {
+                    diagnostics.push(new_diagnostic(
+                        SOURCE,
+                        action.get().range,
+                        DiagnosticSeverity::ERROR,
+                        "action-arguments",
+                        format!(
+                            "`{}` is given {} but has {}, its directionless parameters being bound by the control plane.",
+                            symbol.get_name(),
+                            count(given, "argument"),
+                            count(directional, "directional parameter")
+                        ),
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Reports the `out` parameters of a callable read before being assigned.
+    fn check_body(&self, node: &VisitNode) -> Vec<Diagnostic> {
+        let NodeKind::Node(rule) = &node.get().kind else {
+            return vec![];
+        };
+        let Some((_, body_rule)) = self.config.bodies.iter().find(|(r, _)| r == rule) else {
+            return vec![];
+        };
+        let (Some(callable), Some(body)) = (
+            self.get_declared_symbol(node),
+            node.get_child_of_kind(NodeKind::Node(body_rule.clone())),
+        ) else {
+            return vec![];
+        };
+
+        let out_parameters: Vec<Symbol> = get_parameters(self.symbol_table, self.calls, callable)
+            .into_iter()
+            .filter(|parameter| parameter.get_direction() == Some(Direction::Out))
+            .collect();
+        if out_parameters.is_empty() {
+            return vec![];
+        }
+
+        let mut flow = Flow {
+            out_parameters,
+            reported: HashSet::new(),
+            diagnostics: vec![],
+        };
+        self.walk_block(&body, HashSet::new(), &mut flow);
+
+        flow.diagnostics
+    }
+
+    /// Walks the statements of a block in order, returning the `out` parameters
+    /// assigned once it has run.
+    fn walk_block(
+        &self,
+        block: &VisitNode,
+        mut assigned: HashSet<String>,
+        flow: &mut Flow,
+    ) -> HashSet<String> {
+        // Children are grouped by rule, not in the order they are written
+        let mut statements = block.get_children();
+        statements.sort_by_key(|statement| statement.get().range.start);
+
+        for statement in statements {
+            assigned = self.walk_statement(&statement, assigned, flow);
+        }
+
+        assigned
+    }
+
+    fn walk_statement(
+        &self,
+        statement: &VisitNode,
+        mut assigned: HashSet<String>,
+        flow: &mut Flow,
+    ) -> HashSet<String> {
+        if is_rule(statement, &self.config.block) {
+            return self.walk_block(statement, assigned, flow);
+        }
+
+        if self
+            .config
+            .exits
+            .iter()
+            .any(|rule| is_rule(statement, rule))
+        {
+            self.check_reads(statement, &assigned, &[], flow);
+            // The rest of the block isn't run, it can't read anything
+            return flow
+                .out_parameters
+                .iter()
+                .map(|parameter| parameter.get_name())
+                .collect();
+        }
+
+        if self
+            .config
+            .branches
+            .iter()
+            .any(|rule| is_rule(statement, rule))
+        {
+            let mut alternatives = vec![];
+            self.walk_alternatives(statement, &assigned, flow, &mut alternatives);
+
+            // Without an alternative for every case, e.g. an `if` without `else`,
+            // the branch may run no block at all.
+            if alternatives.len() < 2 {
+                alternatives.push(assigned);
+            }
+            return alternatives
+                .into_iter()
+                .reduce(|all, alternative| all.intersection(&alternative).cloned().collect())
+                .unwrap_or_default();
+        }
+
+        let mut written = vec![];
+        let mut write_only = vec![];
+        if let Some(call) = resolve_call(self.symbol_table, self.calls, statement) {
+            match call.get_signature() {
+                Some(signature) => {
+                    for (index, argument) in call.arguments.iter().enumerate() {
+                        let direction = signature
+                            .get_parameter(index, argument)
+                            .and_then(|parameter| parameter.get_direction());
+                        match direction {
+                            Some(Direction::Out) => {
+                                written.push(get_head(&argument.text).to_string());
+                                write_only.push(argument.range);
+                            }
+                            Some(Direction::InOut) => {
+                                written.push(get_head(&argument.text).to_string())
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                // An unknown callable could write any of its arguments, or the instance
+                // it is called on.
+                None => {
+                    written.push(get_head(&call.callee).to_string());
+                    for argument in &call.arguments {
+                        written.push(get_head(&argument.text).to_string());
+                        write_only.push(argument.range);
+                    }
+                }
+            }
+        }
+        if let Some((target, _)) = self.get_assignment_target(statement) {
+            written.push(get_head(&target).to_string());
+        }
+
+        self.check_reads(statement, &assigned, &write_only, flow);
+        assigned.extend(written);
+
+        assigned
+    }
+
+    /// Walks the blocks a branch can run, each starting from the same assigned parameters.
+    fn walk_alternatives(
+        &self,
+        node: &VisitNode,
+        assigned: &HashSet<String>,
+        flow: &mut Flow,
+        alternatives: &mut Vec<HashSet<String>>,
+    ) {
+        for child in node.get_children() {
+            if is_rule(&child, &self.config.block) {
+                alternatives.push(self.walk_block(&child, assigned.clone(), flow));
+            } else if child.get().symbol == language_def::Symbol::Expression {
+                self.check_reads(&child, assigned, &[], flow);
+            } else {
+                self.walk_alternatives(&child, assigned, flow, alternatives);
+            }
+        }
+    }
+
+    /// Reports the unassigned `out` parameters read in a node, outside of the ranges only written.
+    fn check_reads(
+        &self,
+        node: &VisitNode,
+        assigned: &HashSet<String>,
+        write_only: &[Range],
+        flow: &mut Flow,
+    ) {
+        for usage in node.get_descendants() {
+            if usage.get().symbol != language_def::Symbol::Usage {
+                continue;
+            }
+            let range = usage.get().range;
+            if write_only
+                .iter()
+                .any(|r| r.start <= range.start && range.end <= r.end)
+            {
+                continue;
+            }
+
+            let Some(symbol) = self.get_linked_symbol(&usage) else {
+                continue;
+            };
+            let name = symbol.get_name();
+            let is_out_parameter = flow.out_parameters.iter().any(|parameter| {
+                parameter.get_name() == name
+                    && parameter.get_definition_range() == symbol.get_definition_range()
+            });
+
+            if is_out_parameter && !assigned.contains(&name) && flow.reported.insert(name.clone()) {
+                flow.diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    range,
+                    DiagnosticSeverity::WARNING,
+                    "unassigned-out",
+                    format!(
+                        "`{}` parameter `{}` is read before being assigned.",
+                        Direction::Out,
+                        name
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// The location written by an assignment, and its range.
+    fn get_assignment_target(&self, node: &VisitNode) -> Option<(String, Range)> {
+        let NodeKind::Node(rule) = &node.get().kind else {
+            return None;
+        };
+        let (_, target_rule) = self.config.assignments.iter().find(|(r, _)| r == rule)?;
+        // The same rule can also be a call, which assigns no value
+        node.get_children()
+            .iter()
+            .find(|child| child.get().symbol == language_def::Symbol::Expression)?;
+        let target = node.get_child_of_kind(NodeKind::Node(target_rule.clone()))?;
+
+        Some((compact(&target.get().content), target.get().range))
+    }
+
+    /// Why a location can't be written, if it is rooted in a read-only parameter.
+    fn describe_read_only(&self, location: &str, position: Position) -> Option<&'static str> {
+        let symbol = self
+            .symbol_table
+            .get_symbol_at_pos(get_head(location).to_string(), position)?;
+        if !self.calls.parameters.contains(&symbol.get_kind()) {
+            return None;
+        }
+
+        match symbol.get_direction() {
+            Some(Direction::In) => Some("an `in` parameter"),
+            None => Some("a directionless parameter"),
+            Some(_) => None,
+        }
+    }
+
+    fn get_declared_symbol(&self, node: &VisitNode) -> Option<&Symbol> {
+        let language_def::Symbol::Init { name_node, .. } = &node.get().symbol else {
+            return None;
+        };
+        let name = node.get_child_of_kind(NodeKind::Node(name_node.clone()))?;
+
+        self.get_linked_symbol(&name)
+    }
+
+    fn get_linked_symbol(&self, node: &VisitNode) -> Option<&Symbol> {
+        self.symbol_table
+            .get_symbol(node.get().linked_symbol.clone()?)
+    }
+
+    fn is_action(&self, symbol: &Symbol) -> bool {
+        self.config.actions.contains(&symbol.get_kind())
+    }
+}
+
+/// Name a location is rooted in, e.g. `hdr` for `hdr.stack[0].field`.
+fn get_head(location: &str) -> &str {
+    let end = location.find(['.', '[']).unwrap_or(location.len());
+    &location[..end]
+}
+
+/// Whether a value can be written, being a name followed by members, indexes or slices.
+fn is_location(text: &str) -> bool {
+    let mut depth = 0;
+    let mut outside = String::new();
+    for c in text.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' if depth > 0 => depth -= 1,
+            _ if depth == 0 => outside.push(c),
+            _ => {}
+        }
+    }
+
+    depth == 0 && is_dotted_identifier(&outside)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_location, Directions};
+    use crate::features::diagnostics::provider::tests::get_messages;
+    use crate::metadata::Imports;
+
+    #[test]
+    fn test_is_location() {
+        assert!(is_location("hdr.stack[0].field"));
+        assert!(is_location("x[7:0]"));
+        assert!(!is_location("1"));
+        assert!(!is_location("a+b"));
+        assert!(!is_location("f(x)"));
+    }
+
+    #[test]
+    fn test_directions() {
+        let source = "control C(in bit<8> a, out bit<8> b, inout bit<8> c) {
+    action set(in bit<8> x, out bit<8> y, bit<8> port) {
+        x = 1;
+        port = 2;
+        c = y;
+        y = 3;
+    }
+
+    action misordered(bit<8> port, in bit<8> x) { }
+
+    apply {
+        set(a, 1, 2);
+        set(a, a, 2);
+        set(a, b);
+    }
+}
+
+bit<8> f(out bit<8> r, in bool s) {
+    if (s) {
+        r = 1;
+    } else {
+        r = 2;
+    }
+    return r;
+}
+
+bit<8> g(out bit<8> r, in bool s) {
+    if (s) {
+        r = 1;
+    }
+    return r;
+}
+";
+        let mut messages = get_messages::<Directions>(source, &Imports::default());
+        messages.sort();
+
+        assert_eq!(
+            messages,
+            vec![
+                "Cannot assign to `port`, it is a directionless parameter.",
+                "Cannot assign to `x`, it is an `in` parameter.",
+                "Directional parameter `x` must come before the directionless ones, bound by the control plane.",
+                "`1` can't be passed to the `out` parameter `y`, it isn't a writable location.",
+                "`a` can't be passed to the `out` parameter `y`, `a` is an `in` parameter.",
+                "`out` parameter `r` is read before being assigned.",
+                "`out` parameter `y` is read before being assigned.",
+                "`set` is given 2 arguments but has 3 parameters, its directionless parameters are only bound by the control plane when a table runs it.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::{Arc, Mutex};
+
+use crate::metadata::{AstQuery, Symbol, SymbolTableQuery};
+use crate::settings::Settings;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity};
+
+use super::provider::DiagnosticProvider;
+use super::utils::new_diagnostic;
+
+const SOURCE: &str = "Symbol table";
+
+pub struct Duplicates {}
+
+impl DiagnosticProvider for Duplicates {
+    fn get_diagnostics(
+        _ast_query: &Arc<Mutex<impl AstQuery>>,
+        symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let symbol_table_query = symbol_table_query.lock().unwrap();
+
+        let mut diagnostics: Vec<Diagnostic> = symbol_table_query
+            .get_duplicate_symbols()
+            .into_iter()
+            .map(|(first, duplicate)| {
+                let mut diagnostic = new_diagnostic(
+                    SOURCE,
+                    duplicate.get_definition_range(),
+                    DiagnosticSeverity::ERROR,
+                    "duplicate",
+                    format!("Duplicate declaration of `{}`.", duplicate.get_name()),
+                );
+                diagnostic.related_information = related(&first, "First declared here.");
+                diagnostic
+            })
+            .collect();
+
+        if settings.diagnostics.shadowing {
+            diagnostics.extend(symbol_table_query.get_shadowing_symbols().into_iter().map(
+                |(symbol, shadowed)| {
+                    let mut diagnostic = new_diagnostic(
+                        SOURCE,
+                        symbol.get_definition_range(),
+                        DiagnosticSeverity::WARNING,
+                        "shadowing",
+                        format!(
+                            "Declaration of `{}` shadows a declaration of an enclosing scope.",
+                            symbol.get_name()
+                        ),
+                    );
+                    diagnostic.related_information = related(&shadowed, "Shadowed declaration.");
+                    diagnostic
+                },
+            ));
+        }
+
+        diagnostics
+    }
+}
+
+/// Points from a diagnostic to the declaration of `symbol`.
+fn related(symbol: &Symbol, message: &str) -> Option<Vec<DiagnosticRelatedInformation>> {
+    Some(vec![DiagnosticRelatedInformation {
+        location: symbol.get_definition_location(),
+        message: message.to_string(),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Duplicates;
+    use crate::features::diagnostics::provider::tests::get_messages;
+    use crate::metadata::Imports;
+
+    #[test]
+    fn test_duplicates() {
+        let source = "extern packet_in {
+    void extract<T>(out T hdr);
+    void extract<T>(out T variableSizeHeader, in bit<32> variableFieldSizeInBits);
+}
+
+extern void mark_to_drop();
+extern void mark_to_drop(inout bit<9> egress_spec);
+
+control C(inout bit<32> x) {
+    action a() { }
+    action a() { }
+    apply { }
+}
+";
+        let messages = get_messages::<Duplicates>(source, &Imports::default());
+
+        assert_eq!(messages, vec!["Duplicate declaration of `a`."]);
+    }
+}
@@ -0,0 +1,24 @@
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use crate::include::Include;
+
+use super::utils::new_diagnostic;
+
+pub fn get_include_diagnostics(includes: &[Include]) -> Vec<Diagnostic> {
+    includes
+        .iter()
+        .filter(|include| include.resolved.is_none())
+        .map(|include| {
+            new_diagnostic(
+                "Includes",
+                include.range,
+                DiagnosticSeverity::WARNING,
+                "include",
+                format!(
+                    "Couldn't find `{}`, its symbols won't be available.",
+                    include.path
+                ),
+            )
+        })
+        .collect()
+}
@@ -1,4 +1,16 @@
+mod arguments;
+mod directions;
+mod duplicates;
+mod include;
 mod parse;
 mod provider;
+mod states;
+mod tables;
+mod types;
+mod undefined;
+mod unused;
+mod utils;
 
-pub use provider::{get_full_diagnostics, get_quick_diagnostics};
+pub use include::get_include_diagnostics;
+pub use provider::get_full_diagnostics;
+pub use unused::UNUSED_CODE;
@@ -1,9 +1,11 @@
 use std::sync::{Arc, Mutex};
 
 use crate::metadata::{AstQuery, NodeKind, SymbolTableQuery, VisitNode, Visitable};
+use crate::settings::Settings;
 use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
 
 use super::provider::DiagnosticProvider;
+use super::utils::new_diagnostic;
 
 pub struct Parse {}
 
@@ -11,6 +13,7 @@ impl DiagnosticProvider for Parse {
     fn get_diagnostics(
         ast_query: &Arc<Mutex<impl AstQuery>>,
         _symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        _settings: &Settings,
     ) -> Vec<Diagnostic> {
         let ast_query = ast_query.lock().unwrap();
         let root = ast_query.visit_root();
@@ -24,20 +27,16 @@ impl DiagnosticProvider for Parse {
         errors
             .into_iter()
             .map(|(node, msg)| {
-                Diagnostic::new(
+                new_diagnostic(
+                    "AST",
                     node.get().range,
-                    Some(DiagnosticSeverity::ERROR),
-                    Some(tower_lsp::lsp_types::NumberOrString::String(
-                        "parsing".to_string(),
-                    )),
-                    Some("AST".to_string()),
+                    DiagnosticSeverity::ERROR,
+                    "parsing",
                     if let Some(msg) = msg {
                         format!("Syntax error: {}", msg)
                     } else {
                         "Syntax error.".to_string()
                     },
-                    None,
-                    None,
                 )
             })
             .collect()
@@ -2,8 +2,17 @@ use std::sync::{Arc, Mutex};
 
 use tower_lsp::lsp_types::Diagnostic;
 
+use super::arguments::Arguments;
+use super::directions::Directions;
+use super::duplicates::Duplicates;
 use super::parse::Parse;
+use super::states::States;
+use super::tables::Tables;
+use super::types::Types;
+use super::undefined::Undefined;
+use super::unused::Unused;
 use crate::metadata::{AstQuery, SymbolTableQuery};
+use crate::settings::Settings;
 
 macro_rules! diags {
     ($($diag:expr),*) => {
@@ -23,19 +32,63 @@ pub trait DiagnosticProvider {
     fn get_diagnostics(
         ast_query: &Arc<Mutex<impl AstQuery>>,
         symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        settings: &Settings,
     ) -> Vec<Diagnostic>;
 }
 
-pub fn get_quick_diagnostics(
+pub fn get_full_diagnostics(
     ast_query: &Arc<Mutex<impl AstQuery>>,
     symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+    settings: &Settings,
 ) -> Vec<Diagnostic> {
-    diags![Parse::get_diagnostics(ast_query, symbol_table_query)]
+    diags![
+        Parse::get_diagnostics(ast_query, symbol_table_query, settings),
+        Undefined::get_diagnostics(ast_query, symbol_table_query, settings),
+        Duplicates::get_diagnostics(ast_query, symbol_table_query, settings),
+        Types::get_diagnostics(ast_query, symbol_table_query, settings),
+        States::get_diagnostics(ast_query, symbol_table_query, settings),
+        Tables::get_diagnostics(ast_query, symbol_table_query, settings),
+        Directions::get_diagnostics(ast_query, symbol_table_query, settings),
+        Arguments::get_diagnostics(ast_query, symbol_table_query, settings),
+        Unused::get_diagnostics(ast_query, symbol_table_query, settings)
+    ]
 }
 
-pub fn get_full_diagnostics(
-    ast_query: &Arc<Mutex<impl AstQuery>>,
-    symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
-) -> Vec<Diagnostic> {
-    diags![Parse::get_diagnostics(ast_query, symbol_table_query)]
+#[cfg(test)]
+pub(super) mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tower_lsp::lsp_types::{Diagnostic, Url};
+
+    use super::DiagnosticProvider;
+    use crate::file::tests::new_parser;
+    use crate::metadata::{AstManager, Imports, SymbolTableManager};
+    use crate::settings::Settings;
+
+    /// Messages of the diagnostics reported by `P` for a file, with the default settings.
+    pub fn get_messages<P: DiagnosticProvider>(source: &str, imports: &Imports) -> Vec<String> {
+        get_diagnostics::<P>(source, imports, &Settings::default())
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    pub fn get_diagnostics<P: DiagnosticProvider>(
+        source: &str,
+        imports: &Imports,
+        settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let mut parser = new_parser();
+        let tree = parser.parse(source, None).unwrap();
+        let uri = Url::parse("file:///test.p4").unwrap();
+
+        let mut ast_manager = AstManager::new(source, tree);
+        let st_manager = SymbolTableManager::new(uri, ast_manager.get_ast(), imports);
+
+        P::get_diagnostics(
+            &Arc::new(Mutex::new(ast_manager)),
+            &Arc::new(Mutex::new(st_manager)),
+            settings,
+        )
+    }
 }
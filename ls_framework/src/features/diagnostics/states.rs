@@ -0,0 +1,453 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use indextree::NodeId;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, DiagnosticTag, Range};
+
+use crate::language_def::{self, LanguageDefinition, SelectDetails, StateMachine};
+use crate::metadata::{
+    parse_integer, AstQuery, NodeKind, SymbolTableQuery, Type, TypeChecker, VisitNode, Visitable,
+};
+use crate::settings::Settings;
+
+use super::provider::DiagnosticProvider;
+use super::utils::{count, is_rule, is_wildcard, new_diagnostic, split_keyset};
+
+const SOURCE: &str = "State machine";
+
+pub struct States {}
+
+impl DiagnosticProvider for States {
+    fn get_diagnostics(
+        ast_query: &Arc<Mutex<impl AstQuery>>,
+        symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        _settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let Some(config) = LanguageDefinition::get().state_machine.as_ref() else {
+            return vec![];
+        };
+
+        let ast_query = ast_query.lock().unwrap();
+        let symbol_table_query = symbol_table_query.lock().unwrap();
+        let root = ast_query.visit_root();
+
+        let mut machines = vec![];
+        collect_machines(root, None, root.get_id(), config, &mut machines);
+
+        let final_states = get_final_states(config);
+        let mut diagnostics: Vec<Diagnostic> = machines
+            .iter()
+            .flat_map(|machine| check_machine(machine, config, &final_states))
+            .collect();
+
+        if let Some(select) = &config.select {
+            let mut type_checker = TypeChecker::new(&*symbol_table_query);
+            for node in root.get_descendants() {
+                if is_rule(&node, &select.select) {
+                    diagnostics.append(&mut check_select(node, select, type_checker.as_mut()));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Ranges of the usages naming the next state of a transition, whose undefined
+/// names are reported by this pass.
+pub fn get_next_state_ranges(root: VisitNode) -> Vec<Range> {
+    let Some(config) = LanguageDefinition::get().state_machine.as_ref() else {
+        return vec![];
+    };
+
+    root.get_descendants()
+        .into_iter()
+        .filter(|node| is_rule(node, &config.next_state))
+        .map(|node| node.get().range)
+        .collect()
+}
+
+struct Machine {
+    scope_id: NodeId,
+    /// Name and range of the declaration owning the machine, e.g. a parser.
+    owner: Option<(String, Range)>,
+    states: Vec<State>,
+}
+
+struct State {
+    name: String,
+    range: Range,
+    next_states: Vec<(String, Range)>,
+}
+
+/// Groups the state declarations by the scope declaring them.
+fn collect_machines(
+    node: VisitNode,
+    owner: Option<(String, Range)>,
+    scope_id: NodeId,
+    config: &StateMachine,
+    machines: &mut Vec<Machine>,
+) {
+    for child in node.get_children() {
+        let declaration = get_declaration_name(&child);
+
+        if let (Some((name, range)), language_def::Symbol::Init { kind, .. }) =
+            (&declaration, &child.get().symbol)
+        {
+            if *kind == config.state {
+                let next_states = child
+                    .get_descendants()
+                    .into_iter()
+                    .filter(|node| is_rule(node, &config.next_state))
+                    .map(|node| (node.get().content.trim().to_string(), node.get().range))
+                    .collect();
+                let state = State {
+                    name: name.clone(),
+                    range: *range,
+                    next_states,
+                };
+
+                match machines.iter_mut().find(|m| m.scope_id == scope_id) {
+                    Some(machine) => machine.states.push(state),
+                    None => machines.push(Machine {
+                        scope_id,
+                        owner: owner.clone(),
+                        states: vec![state],
+                    }),
+                }
+                continue;
+            }
+        }
+
+        let child_scope_id = if child.get().kind.is_scope_node() {
+            child.get_id()
+        } else {
+            scope_id
+        };
+        collect_machines(
+            child,
+            declaration.or(owner.clone()),
+            child_scope_id,
+            config,
+            machines,
+        );
+    }
+}
+
+fn check_machine(
+    machine: &Machine,
+    config: &StateMachine,
+    final_states: &HashSet<String>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let is_defined = |name: &String| {
+        final_states.contains(name) || machine.states.iter().any(|state| state.name == *name)
+    };
+
+    let start = machine
+        .states
+        .iter()
+        .find(|state| state.name == config.start);
+    if start.is_none() {
+        let (message, range) = match &machine.owner {
+            Some((name, range)) => (
+                format!("`{}` has no `{}` state.", name, config.start),
+                *range,
+            ),
+            None => (
+                format!("Missing `{}` state.", config.start),
+                machine.states[0].range,
+            ),
+        };
+        diagnostics.push(new_diagnostic(
+            SOURCE,
+            range,
+            DiagnosticSeverity::ERROR,
+            "missing-start",
+            message,
+        ));
+    }
+
+    for state in &machine.states {
+        if state.next_states.is_empty() {
+            diagnostics.push(new_diagnostic(
+                SOURCE,
+                state.range,
+                DiagnosticSeverity::WARNING,
+                "implicit-reject",
+                format!(
+                    "State `{}` has no transition, it implicitly rejects.",
+                    state.name
+                ),
+            ));
+        }
+
+        for (name, range) in &state.next_states {
+            if !is_defined(name) {
+                diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    *range,
+                    DiagnosticSeverity::ERROR,
+                    "undefined-state",
+                    format!("Transition to undefined state `{}`.", name),
+                ));
+            }
+        }
+    }
+
+    if let Some(start) = start {
+        let mut reached: HashSet<&str> = HashSet::from([start.name.as_str()]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(state) = queue.pop_front() {
+            for (name, _) in &state.next_states {
+                if let Some(next) = machine.states.iter().find(|s| s.name == *name) {
+                    if reached.insert(&next.name) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        for state in &machine.states {
+            if !reached.contains(state.name.as_str()) {
+                let mut diagnostic = new_diagnostic(
+                    SOURCE,
+                    state.range,
+                    DiagnosticSeverity::WARNING,
+                    "unreachable-state",
+                    format!(
+                        "State `{}` is unreachable from `{}`.",
+                        state.name, config.start
+                    ),
+                );
+                diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Checks that the keysets of a select have as many values as it selects on,
+/// and that their literals fit in the selected values.
+fn check_select<Q: SymbolTableQuery>(
+    select: VisitNode,
+    details: &SelectDetails,
+    mut type_checker: Option<&mut TypeChecker<Q>>,
+) -> Vec<Diagnostic> {
+    let Some(values) = select.get_child_of_kind(NodeKind::Node(details.values.clone())) else {
+        return vec![];
+    };
+    let value_types: Vec<Type> = values
+        .get_children()
+        .into_iter()
+        .filter(|value| value.get().symbol == language_def::Symbol::Expression)
+        .map(|value| match type_checker.as_mut() {
+            Some(type_checker) => type_checker.infer(value),
+            None => Type::Unknown,
+        })
+        .collect();
+
+    let mut diagnostics = vec![];
+    for case in select.get_descendants() {
+        if !is_rule(&case, &details.case) {
+            continue;
+        }
+        let Some(keyset) = case.get_child_of_kind(NodeKind::Node(details.keyset.clone())) else {
+            continue;
+        };
+        let range = keyset.get().range;
+
+        let text = keyset.get().content.trim();
+        if is_wildcard(text) {
+            continue;
+        }
+        let elements = split_keyset(text);
+
+        if elements.len() != value_types.len() {
+            diagnostics.push(new_diagnostic(
+                SOURCE,
+                range,
+                DiagnosticSeverity::ERROR,
+                "keyset-mismatch",
+                format!(
+                    "The keyset has {} but the select expression has {}.",
+                    count(elements.len(), "value"),
+                    count(value_types.len(), "value")
+                ),
+            ));
+            continue;
+        }
+
+        for (element, value_type) in elements.into_iter().zip(&value_types) {
+            if let Some(message) = check_keyset_element(element, value_type, "selected value") {
+                diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    range,
+                    DiagnosticSeverity::ERROR,
+                    "keyset-mismatch",
+                    message,
+                ));
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Checks the literals of a keyset element, e.g. `0x800`, `8w1 &&& 8w3` or `1 .. 5`,
+/// against the type of the value it is matched with, described by `subject`.
+pub(super) fn check_keyset_element(
+    element: &str,
+    value_type: &Type,
+    subject: &str,
+) -> Option<String> {
+    let (Type::Bit(width) | Type::Int(width)) = value_type else {
+        return None;
+    };
+    if is_wildcard(element) {
+        return None;
+    }
+
+    element
+        .split("&&&")
+        .flat_map(|part| part.split(".."))
+        .map(str::trim)
+        .find_map(|literal| match parse_integer(literal)? {
+            (Type::Bit(w) | Type::Int(w), _) if w != *width => Some(format!(
+                "Keyset value `{}` has {} but the {} of type `{}` has {}.",
+                literal,
+                count(w as usize, "bit"),
+                subject,
+                value_type,
+                count(*width as usize, "bit")
+            )),
+            (Type::InfInt, value) if !fits(value, value_type) => Some(format!(
+                "Keyset value `{}` doesn't fit in the {} of type `{}`.",
+                literal, subject, value_type
+            )),
+            _ => None,
+        })
+}
+
+/// Whether an integer literal without width fits in a value of the given type.
+fn fits(value: i128, value_type: &Type) -> bool {
+    match *value_type {
+        Type::Bit(width) if width < 127 => 0 <= value && value < 1 << width,
+        Type::Int(width) if width < 128 => -(1 << (width - 1)) <= value && value < 1 << (width - 1),
+        _ => true,
+    }
+}
+
+/// Names of the built-in states, which end the machine.
+fn get_final_states(config: &StateMachine) -> HashSet<String> {
+    LanguageDefinition::get()
+        .ast_rules
+        .iter()
+        .flat_map(|rule| rule.builtin_symbols.iter())
+        .filter(|(_, kind)| *kind == config.state)
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Name and range of the symbol declared by a node, if any.
+fn get_declaration_name(node: &VisitNode) -> Option<(String, Range)> {
+    let language_def::Symbol::Init { name_node, .. } = &node.get().symbol else {
+        return None;
+    };
+    let name = node.get_child_of_kind(NodeKind::Node(name_node.clone()))?;
+
+    Some((name.get().content.clone(), name.get().range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_keyset_element, States};
+    use crate::features::diagnostics::provider::tests::get_messages;
+    use crate::metadata::{Imports, Type};
+
+    #[test]
+    fn test_check_keyset_element() {
+        assert_eq!(
+            check_keyset_element("0xFF &&& 0x0F", &Type::Bit(8), "selected value"),
+            None
+        );
+        assert_eq!(
+            check_keyset_element("1 .. 300", &Type::Bool, "selected value"),
+            None
+        );
+        assert_eq!(
+            check_keyset_element("_", &Type::Bit(8), "selected value"),
+            None
+        );
+        assert_eq!(
+            check_keyset_element("1 .. 300", &Type::Bit(8), "selected value").unwrap(),
+            "Keyset value `300` doesn't fit in the selected value of type `bit<8>`."
+        );
+        assert_eq!(
+            check_keyset_element("4w6", &Type::Bit(8), "selected value").unwrap(),
+            "Keyset value `4w6` has 4 bits but the selected value of type `bit<8>` has 8 bits."
+        );
+        assert!(check_keyset_element("-128", &Type::Int(8), "selected value").is_none());
+    }
+
+    #[test]
+    fn test_states() {
+        let source = "header h_t {
+    bit<16> etherType;
+    bit<8> proto;
+}
+
+struct headers_t {
+    h_t h;
+}
+
+parser P(out headers_t hdr) {
+    state start {
+        transition select(hdr.h.etherType) {
+            0x800: parse_ipv4;
+            0x86dd: parse_ipv6;
+            0x10000: accept;
+            (1, 2): accept;
+            default: reject;
+        }
+    }
+
+    state parse_ipv4 {
+        transition select(hdr.h.etherType, hdr.h.proto) {
+            (16w1, 8w6): parse_tcp;
+            (16w1, 4w6): accept;
+        }
+    }
+
+    state parse_tcp { }
+
+    state orphan {
+        transition accept;
+    }
+}
+
+parser Q(out headers_t hdr) {
+    state first {
+        transition accept;
+    }
+}
+";
+        let messages = get_messages::<States>(source, &Imports::default());
+
+        assert_eq!(
+            messages,
+            vec![
+                "Transition to undefined state `parse_ipv6`.",
+                "State `parse_tcp` has no transition, it implicitly rejects.",
+                "State `orphan` is unreachable from `start`.",
+                "`Q` has no `start` state.",
+                "Keyset value `0x10000` doesn't fit in the selected value of type `bit<16>`.",
+                "The keyset has 2 values but the select expression has 1 value.",
+                "Keyset value `4w6` has 4 bits but the selected value of type `bit<8>` has 8 bits.",
+            ]
+        );
+    }
+}
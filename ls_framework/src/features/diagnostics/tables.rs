@@ -0,0 +1,482 @@
+use std::sync::{Arc, Mutex};
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Range, Url};
+
+use crate::language_def::{self, LanguageDefinition, TableDetails};
+use crate::metadata::{
+    parse_integer, AstQuery, NodeKind, Symbol, SymbolTableQuery, Type, TypeChecker, VisitNode,
+    Visitable,
+};
+use crate::settings::Settings;
+use crate::utils::{compact_spaces, is_dotted_identifier};
+
+use super::provider::DiagnosticProvider;
+use super::states::check_keyset_element;
+use super::utils::{count, count_arguments, is_rule, is_wildcard, new_diagnostic, split_keyset};
+
+const SOURCE: &str = "Table";
+
+pub struct Tables {}
+
+impl DiagnosticProvider for Tables {
+    fn get_diagnostics(
+        ast_query: &Arc<Mutex<impl AstQuery>>,
+        symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        _settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let Some(config) = LanguageDefinition::get().tables.as_ref() else {
+            return vec![];
+        };
+
+        let ast_query = ast_query.lock().unwrap();
+        let symbol_table_query = symbol_table_query.lock().unwrap();
+        let root = ast_query.visit_root();
+
+        let (properties, match_kinds) =
+            get_allowed_names(config, &symbol_table_query.get_import_uris());
+        let checker = TableChecker {
+            config,
+            symbol_table: &*symbol_table_query,
+            properties,
+            match_kinds,
+        };
+        let mut type_checker = TypeChecker::new(&*symbol_table_query);
+
+        root.get_descendants()
+            .into_iter()
+            .filter(|node| is_rule(node, &config.table))
+            .flat_map(|table| checker.check(table, type_checker.as_mut()))
+            .collect()
+    }
+}
+
+/// Ranges of the usages naming the actions of a table, whose undefined names
+/// are reported by this pass.
+pub fn get_action_ranges(root: VisitNode) -> Vec<Range> {
+    let Some(config) = LanguageDefinition::get().tables.as_ref() else {
+        return vec![];
+    };
+
+    root.get_descendants()
+        .into_iter()
+        .filter(|node| is_rule(node, &config.actions))
+        .flat_map(|list| {
+            get_action_usages(&list)
+                .into_iter()
+                .map(|usage| usage.get().range)
+                .collect::<Vec<Range>>()
+        })
+        .collect()
+}
+
+/// Properties and match kinds of the architecture included by the file, or of
+/// all of them when none is.
+fn get_allowed_names(config: &TableDetails, imports: &[Url]) -> (Vec<String>, Vec<String>) {
+    let included: Vec<&language_def::Architecture> = config
+        .architectures
+        .iter()
+        .filter(|architecture| {
+            imports.iter().any(|uri| {
+                uri.path_segments()
+                    .and_then(|mut segments| segments.next_back())
+                    .is_some_and(|file| file == architecture.file)
+            })
+        })
+        .collect();
+    let architectures = if included.is_empty() {
+        config.architectures.iter().collect()
+    } else {
+        included
+    };
+
+    let mut properties = config.properties.clone();
+    let mut match_kinds = config.match_kinds.clone();
+    for architecture in architectures {
+        properties.extend(architecture.properties.iter().cloned());
+        match_kinds.extend(architecture.match_kinds.iter().cloned());
+    }
+
+    (properties, match_kinds)
+}
+
+struct TableChecker<'a, Q: SymbolTableQuery> {
+    config: &'a TableDetails,
+    symbol_table: &'a Q,
+    properties: Vec<String>,
+    match_kinds: Vec<String>,
+}
+
+struct Key {
+    expression: String,
+    value_type: Type,
+}
+
+impl<Q: SymbolTableQuery> TableChecker<'_, Q> {
+    fn check(
+        &self,
+        table: VisitNode,
+        type_checker: Option<&mut TypeChecker<Q>>,
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        let keys = self.check_keys(&table, type_checker, &mut diagnostics);
+        let actions = self.check_actions(&table, &mut diagnostics);
+        self.check_properties(&table, &actions, &mut diagnostics);
+        self.check_entries(&table, &keys, &mut diagnostics);
+
+        diagnostics
+    }
+
+    /// Reports the unknown match kinds and the keys matching the same expression twice.
+    fn check_keys(
+        &self,
+        table: &VisitNode,
+        mut type_checker: Option<&mut TypeChecker<Q>>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<Key> {
+        let mut keys: Vec<Key> = vec![];
+
+        for key in get_nodes(table, &self.config.key) {
+            let Some(expression) = get_child(&key, &self.config.key_expression) else {
+                continue;
+            };
+            let text = compact_spaces(&expression.get().content);
+
+            if let Some(match_kind) = get_child(&key, &self.config.match_kind) {
+                let name = match_kind.get().content.trim();
+                if !self.match_kinds.iter().any(|kind| kind == name) {
+                    diagnostics.push(new_diagnostic(
+                        SOURCE,
+                        match_kind.get().range,
+                        DiagnosticSeverity::ERROR,
+                        "unknown-match-kind",
+                        format!("Unknown match kind `{}`.", name),
+                    ));
+                }
+            }
+
+            if keys.iter().any(|key| key.expression == text) {
+                diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    expression.get().range,
+                    DiagnosticSeverity::ERROR,
+                    "duplicate-key",
+                    format!("Duplicate key `{}`.", text),
+                ));
+            }
+
+            let value_type = match type_checker.as_mut() {
+                Some(type_checker) => type_checker.infer(expression),
+                None => Type::Unknown,
+            };
+            keys.push(Key {
+                expression: text,
+                value_type,
+            });
+        }
+
+        keys
+    }
+
+    /// Reports the names of the action list that aren't actions, and returns those listed.
+    fn check_actions(&self, table: &VisitNode, diagnostics: &mut Vec<Diagnostic>) -> Vec<String> {
+        let mut actions = vec![];
+
+        for list in get_nodes(table, &self.config.actions) {
+            for usage in get_action_usages(&list) {
+                let name = usage.get().content.trim().to_string();
+                let range = usage.get().range;
+
+                match self.get_symbol(&usage) {
+                    Some(symbol) if !self.is_action(symbol) => {
+                        diagnostics.push(new_diagnostic(
+                            SOURCE,
+                            range,
+                            DiagnosticSeverity::ERROR,
+                            "not-an-action",
+                            format!("`{}` is not an action.", name),
+                        ));
+                    }
+                    // Names could come from an include that wasn't found
+                    None if !self.symbol_table.has_unresolved_imports() => {
+                        diagnostics.push(new_diagnostic(
+                            SOURCE,
+                            range,
+                            DiagnosticSeverity::ERROR,
+                            "undefined-action",
+                            format!("Undefined action `{}`.", name),
+                        ));
+                    }
+                    _ => {}
+                }
+                actions.push(name);
+            }
+        }
+
+        actions
+    }
+
+    /// Reports the unknown properties, the default action and the size.
+    fn check_properties(
+        &self,
+        table: &VisitNode,
+        actions: &[String],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for property in get_nodes(table, &self.config.property) {
+            let Some(name) = get_child(&property, &self.config.property_name) else {
+                continue;
+            };
+            let name_text = name.get().content.trim();
+            let value = get_child(&property, &self.config.property_value);
+
+            if !self.properties.iter().any(|p| p == name_text) {
+                diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    name.get().range,
+                    DiagnosticSeverity::WARNING,
+                    "unknown-property",
+                    format!("Unknown table property `{}`.", name_text),
+                ));
+            } else if name_text == self.config.default_action {
+                if let Some(value) = value {
+                    diagnostics.extend(self.check_default_action(value, actions));
+                }
+            } else if name_text == self.config.size {
+                if let Some(value) = value {
+                    diagnostics.extend(check_size(value));
+                }
+            }
+        }
+    }
+
+    /// The default action must be listed in the actions, and called with all its parameters.
+    fn check_default_action(&self, value: VisitNode, actions: &[String]) -> Option<Diagnostic> {
+        let text = compact_spaces(&value.get().content);
+        let (name, arguments) = match text.find('(') {
+            Some(i) => (text[..i].trim(), Some(&text[i..])),
+            None => (text.as_str(), None),
+        };
+        let range = value.get().range;
+
+        if !actions.iter().any(|action| action == name) {
+            return Some(new_diagnostic(
+                SOURCE,
+                range,
+                DiagnosticSeverity::ERROR,
+                "default-action",
+                format!(
+                    "Default action `{}` isn't in the actions of the table.",
+                    name
+                ),
+            ));
+        }
+
+        let symbol = value
+            .get_descendants()
+            .iter()
+            .find_map(|node| self.get_symbol(node))
+            .filter(|symbol| self.is_action(symbol))?;
+        let expected = self
+            .symbol_table
+            .get_symbols_in_scope(symbol.get_field_scope_id()?)
+            .iter()
+            .filter(|s| self.config.action_parameters.contains(&s.get_kind()))
+            .count();
+        let given = arguments.map_or(0, count_arguments);
+
+        (expected != given).then(|| {
+            new_diagnostic(
+                SOURCE,
+                range,
+                DiagnosticSeverity::ERROR,
+                "default-action",
+                format!(
+                    "Default action `{}` takes {} but {} given.",
+                    name,
+                    count(expected, "argument"),
+                    if given == 1 {
+                        "1 was".to_string()
+                    } else {
+                        format!("{} were", given)
+                    }
+                ),
+            )
+        })
+    }
+
+    /// Reports the constant entries whose keysets don't match the keys.
+    fn check_entries(&self, table: &VisitNode, keys: &[Key], diagnostics: &mut Vec<Diagnostic>) {
+        if keys.is_empty() {
+            return;
+        }
+
+        for entry in get_nodes(table, &self.config.entry) {
+            let Some(keyset) = get_child(&entry, &self.config.entry_keyset) else {
+                continue;
+            };
+            let range = keyset.get().range;
+
+            let text = keyset.get().content.trim();
+            if is_wildcard(text) {
+                continue;
+            }
+            let elements = split_keyset(text);
+
+            if elements.len() != keys.len() {
+                diagnostics.push(new_diagnostic(
+                    SOURCE,
+                    range,
+                    DiagnosticSeverity::ERROR,
+                    "entry-mismatch",
+                    format!(
+                        "The keyset has {} but the table has {}.",
+                        count(elements.len(), "value"),
+                        count(keys.len(), "key")
+                    ),
+                ));
+                continue;
+            }
+
+            for (element, key) in elements.into_iter().zip(keys) {
+                let subject = format!("key `{}`", key.expression);
+                if let Some(message) = check_keyset_element(element, &key.value_type, &subject) {
+                    diagnostics.push(new_diagnostic(
+                        SOURCE,
+                        range,
+                        DiagnosticSeverity::ERROR,
+                        "entry-mismatch",
+                        message,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn get_symbol(&self, node: &VisitNode) -> Option<&Symbol> {
+        self.symbol_table
+            .get_symbol(node.get().linked_symbol.clone()?)
+    }
+
+    fn is_action(&self, symbol: &Symbol) -> bool {
+        self.config.action_types.contains(&symbol.get_kind())
+    }
+}
+
+/// Usages naming the actions of an action list.
+fn get_action_usages<'a>(list: &'a VisitNode) -> Vec<VisitNode<'a>> {
+    list.get_descendants()
+        .into_iter()
+        .filter(|node| node.get().symbol == language_def::Symbol::Usage)
+        .collect()
+}
+
+fn get_nodes<'a>(table: &'a VisitNode, rule: &str) -> Vec<VisitNode<'a>> {
+    table
+        .get_descendants()
+        .into_iter()
+        .filter(|node| is_rule(node, rule))
+        .collect()
+}
+
+fn get_child<'a>(node: &'a VisitNode, rule: &str) -> Option<VisitNode<'a>> {
+    node.get_child_of_kind(NodeKind::Node(rule.to_string()))
+}
+
+/// A size must be a positive integer, constants are left to the compiler.
+fn check_size(value: VisitNode) -> Option<Diagnostic> {
+    let text = value.get().content.trim();
+    let is_positive = match parse_integer(text) {
+        Some((_, value)) => value > 0,
+        None => is_dotted_identifier(text),
+    };
+
+    (!is_positive).then(|| {
+        new_diagnostic(
+            SOURCE,
+            value.get().range,
+            DiagnosticSeverity::ERROR,
+            "invalid-size",
+            format!("Table size `{}` isn't a positive integer.", text),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tables;
+    use crate::features::diagnostics::provider::tests::get_messages;
+    use crate::metadata::Imports;
+
+    #[test]
+    fn test_tables() {
+        let source = "header h_t {
+    bit<16> etherType;
+    bit<8> proto;
+}
+
+struct headers_t {
+    h_t h;
+}
+
+control C(inout headers_t hdr) {
+    bit<8> port;
+
+    action drop() { }
+    action set_port(bit<8> p) {
+        port = p;
+    }
+
+    table forward {
+        key = {
+            hdr.h.etherType: exact;
+            hdr.h.proto: fuzzy;
+            hdr.h.etherType: exact;
+        }
+        actions = {
+            drop;
+            set_port;
+            port;
+            missing;
+        }
+        default_action = set_port();
+        size = 0;
+        counters = 1;
+        const entries = {
+            (0x800, 6, 1): drop();
+            (0x800, 8w6): drop();
+            (0x10000, 6, 1): drop();
+            (0x800, 4w6, 1): drop();
+        }
+    }
+
+    table other {
+        actions = {
+            drop;
+        }
+        default_action = set_port(1);
+        size = 1024;
+    }
+
+    apply { }
+}
+";
+        let messages = get_messages::<Tables>(source, &Imports::default());
+
+        assert_eq!(
+            messages,
+            vec![
+                "Unknown match kind `fuzzy`.",
+                "Duplicate key `hdr.h.etherType`.",
+                "`port` is not an action.",
+                "Undefined action `missing`.",
+                "Default action `set_port` takes 1 argument but 0 were given.",
+                "Table size `0` isn't a positive integer.",
+                "The keyset has 2 values but the table has 3 keys.",
+                "Keyset value `0x10000` doesn't fit in the key `hdr.h.etherType` of type `bit<16>`.",
+                "Keyset value `4w6` has 4 bits but the key `hdr.h.proto` of type `bit<8>` has 8 bits.",
+                "Default action `set_port` isn't in the actions of the table.",
+            ]
+        );
+    }
+}
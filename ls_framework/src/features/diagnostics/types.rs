@@ -0,0 +1,39 @@
+use std::sync::{Arc, Mutex};
+
+use crate::metadata::{AstQuery, SymbolTableQuery, TypeChecker};
+use crate::settings::Settings;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::provider::DiagnosticProvider;
+use super::utils::new_diagnostic;
+
+pub struct Types {}
+
+impl DiagnosticProvider for Types {
+    fn get_diagnostics(
+        ast_query: &Arc<Mutex<impl AstQuery>>,
+        symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        _settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let ast_query = ast_query.lock().unwrap();
+        let symbol_table_query = symbol_table_query.lock().unwrap();
+
+        let Some(type_checker) = TypeChecker::new(&*symbol_table_query) else {
+            return vec![];
+        };
+
+        type_checker
+            .check(ast_query.visit_root())
+            .into_iter()
+            .map(|error| {
+                new_diagnostic(
+                    "Type checker",
+                    error.range,
+                    DiagnosticSeverity::ERROR,
+                    "type-mismatch",
+                    error.message,
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::{Arc, Mutex};
+
+use crate::language_def::LanguageDefinition;
+use crate::metadata::{AstQuery, Symbol, SymbolTableQuery};
+use crate::settings::Settings;
+use crate::utils::is_identifier;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::provider::DiagnosticProvider;
+use super::states::get_next_state_ranges;
+use super::tables::get_action_ranges;
+use super::utils::new_diagnostic;
+
+pub struct Undefined {}
+
+impl DiagnosticProvider for Undefined {
+    fn get_diagnostics(
+        ast_query: &Arc<Mutex<impl AstQuery>>,
+        symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        _settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let symbol_table_query = symbol_table_query.lock().unwrap();
+
+        // Undefined states and actions are reported with the other errors of the
+        // state machine and of the table
+        let root = ast_query.lock().unwrap();
+        let reported = [
+            get_next_state_ranges(root.visit_root()),
+            get_action_ranges(root.visit_root()),
+        ]
+        .concat();
+
+        let keywords = LanguageDefinition::get_keywords();
+        symbol_table_query
+            .get_undefined_symbols()
+            .into_iter()
+            .filter(|symbol| is_identifier(&symbol.name) && !keywords.contains(&symbol.name))
+            .filter(|symbol| !reported.contains(&symbol.range))
+            .filter_map(|symbol| {
+                let visible = symbol_table_query.get_symbols_at_pos(symbol.range.start);
+                let suggestion = find_closest(&symbol.name, &visible);
+
+                // Names could come from an include that wasn't found, reporting them
+                // would only be noise. Near misses of a visible name are typos though.
+                if symbol_table_query.has_unresolved_imports() && suggestion.is_none() {
+                    return None;
+                }
+
+                let scope = match symbol.scope_name {
+                    Some(name) => format!("scope `{}`", name),
+                    None => "the global scope".to_string(),
+                };
+                let mut message = format!("Undefined identifier `{}` in {}", symbol.name, scope);
+                match suggestion {
+                    Some(name) => message.push_str(&format!(", did you mean `{}`?", name)),
+                    None => message.push('.'),
+                }
+
+                Some(new_diagnostic(
+                    "Symbol table",
+                    symbol.range,
+                    DiagnosticSeverity::ERROR,
+                    "undefined",
+                    message,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Visible name closest to an undefined one, if it is close enough to be what was meant.
+fn find_closest(name: &str, visible: &[Symbol]) -> Option<String> {
+    // Short names are close to too many others to allow more than one typo
+    let max_distance = if name.chars().count() <= 4 { 1 } else { 2 };
+
+    visible
+        .iter()
+        .map(|symbol| (edit_distance(name, &symbol.get_name()), symbol.get_name()))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance between two names, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, Undefined};
+    use crate::features::diagnostics::provider::tests::get_messages;
+    use crate::metadata::Imports;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("meta", "meta"), 0);
+        assert_eq!(edit_distance("metaa", "meta"), 1);
+        assert_eq!(edit_distance("hrd", "hdr"), 2);
+        assert_eq!(edit_distance("", "hdr"), 3);
+    }
+
+    #[test]
+    fn test_undefined_with_unresolved_include() {
+        let source = "#include \"missing.p4\"
+
+control C(inout bit<32> meta) {
+    apply {
+        metaa = 1;
+        meta = from_missing_include;
+    }
+}
+";
+        let imports = Imports {
+            unresolved: true,
+            ..Default::default()
+        };
+        let messages = get_messages::<Undefined>(source, &imports);
+
+        assert_eq!(
+            messages,
+            vec!["Undefined identifier `metaa` in scope `C`, did you mean `meta`?"]
+        );
+    }
+}
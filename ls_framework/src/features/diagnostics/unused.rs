@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+
+use crate::metadata::{AstQuery, SymbolTableQuery};
+use crate::settings::Settings;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, DiagnosticTag};
+
+use super::provider::DiagnosticProvider;
+use super::utils::new_diagnostic;
+
+pub const UNUSED_CODE: &str = "unused";
+
+pub struct Unused {}
+
+impl DiagnosticProvider for Unused {
+    fn get_diagnostics(
+        _ast_query: &Arc<Mutex<impl AstQuery>>,
+        symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+        settings: &Settings,
+    ) -> Vec<Diagnostic> {
+        let settings = &settings.diagnostics.unused;
+        if !settings.enabled {
+            return vec![];
+        }
+
+        symbol_table_query
+            .lock()
+            .unwrap()
+            .get_all_symbols()
+            .into_iter()
+            .filter(|symbol| {
+                symbol.get_usages().is_empty()
+                    && !symbol.is_builtin()
+                    && !settings.ignored_kinds.contains(&symbol.get_kind())
+                    && !settings.ignored_names.contains(&symbol.get_name())
+            })
+            .map(|symbol| {
+                let mut diagnostic = new_diagnostic(
+                    "Symbol table",
+                    symbol.get_definition_range(),
+                    DiagnosticSeverity::HINT,
+                    UNUSED_CODE,
+                    format!("`{}` is never used.", symbol.get_name()),
+                );
+                // Editors fade the declaration out rather than underlining it
+                diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+                diagnostic
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag};
+
+    use super::Unused;
+    use crate::features::diagnostics::provider::tests::{get_diagnostics, get_messages};
+    use crate::metadata::Imports;
+    use crate::settings::{Settings, UnusedSettings};
+
+    const SOURCE: &str = "header h_t {
+    bit<8> f;
+}
+
+const bit<8> USED = 1;
+const bit<8> UNUSED = 2;
+
+control C(inout h_t h) {
+    bit<8> tmp;
+
+    action a() {
+        h.f = USED;
+    }
+
+    apply {
+        a();
+    }
+}
+
+C() main;
+";
+
+    #[test]
+    fn test_unused() {
+        // Fields, parameters and `main` are ignored by default
+        let mut messages = get_messages::<Unused>(SOURCE, &Imports::default());
+        messages.sort();
+        assert_eq!(
+            messages,
+            vec!["`UNUSED` is never used.", "`tmp` is never used."]
+        );
+
+        let mut settings = Settings::default();
+        settings.diagnostics.unused = UnusedSettings {
+            enabled: true,
+            ignored_kinds: vec!["Constant".to_string(), "Field".to_string()],
+            ignored_names: vec!["tmp".to_string()],
+        };
+        let diagnostics = get_diagnostics::<Unused>(SOURCE, &Imports::default(), &settings);
+        let mut messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            vec!["`h` is never used.", "`main` is never used."]
+        );
+        assert!(diagnostics.iter().all(|diagnostic| {
+            diagnostic.severity == Some(DiagnosticSeverity::HINT)
+                && diagnostic.tags == Some(vec![DiagnosticTag::UNNECESSARY])
+        }));
+
+        settings.diagnostics.unused.enabled = false;
+        assert!(get_diagnostics::<Unused>(SOURCE, &Imports::default(), &settings).is_empty());
+    }
+}
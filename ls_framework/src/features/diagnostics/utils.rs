@@ -0,0 +1,93 @@
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Range};
+
+use crate::metadata::{NodeKind, VisitNode, Visitable};
+
+/// Diagnostic with a code, reported by the check named `source`.
+pub fn new_diagnostic(
+    source: &str,
+    range: Range,
+    severity: DiagnosticSeverity,
+    code: &str,
+    message: String,
+) -> Diagnostic {
+    Diagnostic::new(
+        range,
+        Some(severity),
+        Some(NumberOrString::String(code.to_string())),
+        Some(source.to_string()),
+        message,
+        None,
+        None,
+    )
+}
+
+pub fn is_rule(node: &VisitNode, name: &str) -> bool {
+    matches!(&node.get().kind, NodeKind::Node(kind) if kind == name)
+}
+
+/// Quantity followed by a noun, plural unless there is one, e.g. `2 bits`.
+pub fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+/// Elements of a keyset, a tuple of keysets being written `(a, b)`.
+pub fn split_keyset(text: &str) -> Vec<&str> {
+    let Some(inner) = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) else {
+        return vec![text];
+    };
+
+    let mut elements = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                // The parentheses were those of an expression, e.g. `(a) + (b)`
+                if depth < 0 {
+                    return vec![text];
+                }
+            }
+            ',' if depth == 0 => {
+                elements.push(inner[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(inner[start..].trim());
+
+    elements
+}
+
+pub fn is_wildcard(text: &str) -> bool {
+    text == "default" || text == "_"
+}
+
+/// Number of arguments of a call, given its arguments in parentheses.
+pub fn count_arguments(arguments: &str) -> usize {
+    match split_keyset(arguments).as_slice() {
+        [""] => 0,
+        elements => elements.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_arguments, split_keyset};
+
+    #[test]
+    fn test_split_keyset() {
+        assert_eq!(split_keyset("0x800"), vec!["0x800"]);
+        assert_eq!(split_keyset("(1, (2), _)"), vec!["1", "(2)", "_"]);
+        assert_eq!(split_keyset("(a) + (b)"), vec!["(a) + (b)"]);
+    }
+
+    #[test]
+    fn test_count_arguments() {
+        assert_eq!(count_arguments("()"), 0);
+        assert_eq!(count_arguments("((a))"), 1);
+        assert_eq!(count_arguments("(f(a, b), 2)"), 2);
+    }
+}
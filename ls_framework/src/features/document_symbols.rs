@@ -0,0 +1,134 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    language_def::LanguageDefinition,
+    metadata::{ScopeId, SymbolTableQuery},
+};
+use tower_lsp::lsp_types::{DocumentSymbol, SymbolKind};
+
+pub fn get_document_symbols(
+    symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+) -> Option<Vec<DocumentSymbol>> {
+    let symbol_table_query = symbol_table_query.lock().unwrap();
+    let root_id = symbol_table_query.get_root_scope_id()?;
+
+    Some(collect_scope(&*symbol_table_query, root_id))
+}
+
+/// Builds the outline of a scope. Symbols owning a scope (e.g. a control)
+/// get its content as children, while the content of anonymous scopes (e.g.
+/// blocks) is hoisted into the enclosing one.
+fn collect_scope(
+    symbol_table_query: &impl SymbolTableQuery,
+    scope_id: ScopeId,
+) -> Vec<DocumentSymbol> {
+    let symbols = symbol_table_query.get_symbols_in_scope(scope_id);
+    let mut document_symbols: Vec<DocumentSymbol> = vec![];
+
+    // Symbols declared by the language (e.g. the final states of a parser)
+    // have nothing to navigate to
+    for symbol in symbols.iter().filter(|symbol| !symbol.is_builtin()) {
+        let Some(kind) = get_symbol_outline_type(&symbol.get_kind()) else {
+            continue;
+        };
+
+        let selection_range = symbol.get_definition_range();
+        let field_scope_id = symbol.get_field_scope_id();
+        let range = field_scope_id
+            .and_then(|id| symbol_table_query.get_scope_range(id))
+            .filter(|range| {
+                range.start <= selection_range.start && selection_range.end <= range.end
+            })
+            .unwrap_or(selection_range);
+        let children = field_scope_id
+            .map(|id| collect_scope(symbol_table_query, id))
+            .filter(|children| !children.is_empty());
+
+        #[allow(deprecated)]
+        document_symbols.push(DocumentSymbol {
+            name: symbol.get_name(),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range,
+            selection_range,
+            children,
+        });
+    }
+
+    for child_id in symbol_table_query.get_child_scopes(scope_id) {
+        if !symbols
+            .iter()
+            .any(|symbol| symbol.get_field_scope_id() == Some(child_id))
+        {
+            document_symbols.append(&mut collect_scope(symbol_table_query, child_id));
+        }
+    }
+
+    document_symbols.sort_by_key(|symbol| symbol.selection_range.start);
+    document_symbols
+}
+
+fn get_symbol_outline_type(symbol_kind: &str) -> Option<SymbolKind> {
+    Some(
+        LanguageDefinition::get()
+            .symbol_types
+            .iter()
+            .find(|symbol_type| symbol_type.name == symbol_kind)?
+            .outline_type
+            .as_ref()?
+            .get(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tower_lsp::lsp_types::{DocumentSymbol, SymbolKind, Url};
+
+    use super::get_document_symbols;
+    use crate::file::tests::new_parser;
+    use crate::metadata::{AstManager, Imports, SymbolTableManager};
+
+    #[test]
+    fn test_parser_states() {
+        let source = "parser P(packet_in pkt) {
+    state start {
+        transition parse_ethernet;
+    }
+
+    state parse_ethernet {
+        transition accept;
+    }
+}
+";
+        let mut parser = new_parser();
+        let tree = parser.parse(source, None).unwrap();
+        let uri = Url::parse("file:///test.p4").unwrap();
+
+        let mut ast_manager = AstManager::new(source, tree);
+        let st_manager = SymbolTableManager::new(uri, ast_manager.get_ast(), &Imports::default());
+
+        let outline = get_document_symbols(&Arc::new(Mutex::new(st_manager))).unwrap();
+        let names = |symbols: &[DocumentSymbol]| -> Vec<(String, SymbolKind)> {
+            symbols
+                .iter()
+                .map(|symbol| (symbol.name.clone(), symbol.kind))
+                .collect()
+        };
+
+        assert_eq!(
+            names(&outline),
+            vec![("P".to_string(), SymbolKind::FUNCTION)]
+        );
+        assert_eq!(
+            names(outline[0].children.as_deref().unwrap()),
+            vec![
+                ("start".to_string(), SymbolKind::ENUM_MEMBER),
+                ("parse_ethernet".to_string(), SymbolKind::ENUM_MEMBER),
+            ]
+        );
+    }
+}
@@ -1,13 +1,13 @@
 use std::sync::{Arc, Mutex};
 
 use crate::metadata::{AstQuery, SymbolTableQuery, Visitable};
-use tower_lsp::lsp_types::{Position, Range};
+use tower_lsp::lsp_types::{Location, Position};
 
-pub fn get_definition_range(
+pub fn get_definition_location(
     ast_query: &Arc<Mutex<impl AstQuery>>,
     symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
     position: Position,
-) -> Option<Range> {
+) -> Option<Location> {
     let ast_query = ast_query.lock().unwrap();
     let root_visit = ast_query.visit_root();
     let node = root_visit.get_node_at_position(position)?;
@@ -15,5 +15,10 @@ pub fn get_definition_range(
     let symbol_table_query = symbol_table_query.lock().unwrap();
     let symbol = symbol_table_query.get_symbol(node.get().linked_symbol.clone()?)?;
 
-    Some(symbol.get_definition_range())
+    // Built-in symbols aren't written anywhere
+    if symbol.is_builtin() {
+        return None;
+    }
+
+    Some(symbol.get_definition_location())
 }
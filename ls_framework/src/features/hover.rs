@@ -1,8 +1,16 @@
 use std::sync::{Arc, Mutex};
 
-use tower_lsp::lsp_types::{HoverContents, MarkedString, Position};
+use tower_lsp::lsp_types::{HoverContents, MarkupContent, MarkupKind, Position};
 
-use crate::metadata::{AstQuery, SymbolTableQuery, Visitable};
+use crate::language_def::{self, HoverDetails, LanguageDefinition, TableDetails};
+use crate::metadata::{
+    AstQuery, NodeKind, Symbol, SymbolTableQuery, Type, TypeChecker, TypeDecType, VisitNode,
+    Visitable,
+};
+use crate::utils::compact_spaces;
+
+/// Declarations longer than this are cut in the hover.
+const MAX_DECLARATION_LINES: usize = 20;
 
 pub fn get_hover_info(
     ast_query: &Arc<Mutex<impl AstQuery>>,
@@ -14,13 +22,429 @@ pub fn get_hover_info(
     let node = root_visit.get_node_at_position(position)?;
 
     let st_query = symbol_table_query.lock().unwrap();
-    let symbol = st_query.get_symbol(node.get().linked_symbol.clone()?)?;
+    let symbol_id = node.get().linked_symbol.clone()?;
+    let symbol = st_query.get_symbol(symbol_id.clone())?;
+
+    let hover = Hover {
+        symbol_table: &*st_query,
+        type_checker: TypeChecker::new(&*st_query),
+        details: LanguageDefinition::get().hover.as_ref(),
+        position,
+    };
+
+    let mut sections = vec![hover.render_declaration(symbol)];
+    sections.extend(hover.render_type(symbol));
+    sections.extend(hover.render_signature(symbol));
+    sections.extend(hover.render_fields(symbol));
+
+    // Tables are described by their declaration, only found in the hovered file.
+    let declaration = root_visit.get_descendants().into_iter().find(|node| {
+        let language_def::Symbol::Init { name_node, .. } = &node.get().symbol else {
+            return false;
+        };
+        node.get_child_of_kind(NodeKind::Node(name_node.clone()))
+            .is_some_and(|name| name.get().linked_symbol.as_ref() == Some(&symbol_id))
+    });
+    if let (Some(declaration), Some(table)) = (declaration, &LanguageDefinition::get().tables) {
+        sections.extend(render_table(declaration, table));
+    }
+
+    Some(HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: sections.join("\n\n---\n\n"),
+    }))
+}
+
+struct Hover<'a, Q: SymbolTableQuery> {
+    symbol_table: &'a Q,
+    type_checker: Option<TypeChecker<'a, Q>>,
+    details: Option<&'static HoverDetails>,
+    position: Position,
+}
+
+impl<Q: SymbolTableQuery> Hover<'_, Q> {
+    fn render_declaration(&self, symbol: &Symbol) -> String {
+        let name = symbol.get_name();
+        let mut lines: Vec<&str> = symbol.get_declaration().lines().collect();
+        if lines.is_empty() {
+            lines.push(&name);
+        }
+
+        let mut declaration = dedent(&lines[..lines.len().min(MAX_DECLARATION_LINES)]);
+        if lines.len() > MAX_DECLARATION_LINES {
+            declaration.push_str("\n...");
+        }
+
+        format!(
+            "```{}\n{}\n```",
+            LanguageDefinition::get().language.name.to_lowercase(),
+            declaration
+        )
+    }
+
+    /// Type of a value, or aliased type of a typedef.
+    fn render_type(&self, symbol: &Symbol) -> Option<String> {
+        if self.is_callable(symbol) {
+            return None;
+        }
+
+        match symbol.get_type_declaration() {
+            Some(TypeDecType::TypeDef) => {
+                let resolved = self.resolve(&symbol.get_name())?;
+                Some(format!(
+                    "`{}` = {}",
+                    symbol.get_name(),
+                    describe_type(&resolved.to_string(), &resolved)
+                ))
+            }
+            Some(_) => None,
+            None => {
+                let type_name = symbol.get_type_name()?;
+                let resolved = self.resolve(type_name).unwrap_or(Type::Unknown);
+                Some(format!(
+                    "`{}`: {}",
+                    symbol.get_name(),
+                    describe_type(type_name, &resolved)
+                ))
+            }
+        }
+    }
+
+    /// Parameters and returned type of a callable.
+    fn render_signature(&self, symbol: &Symbol) -> Option<String> {
+        if !self.is_callable(symbol) {
+            return None;
+        }
+        let details = self.details?;
+
+        let mut parameters: Vec<Symbol> = symbol
+            .get_field_scope_id()
+            .map(|scope_id| self.symbol_table.get_symbols_in_scope(scope_id))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|s| details.parameters.contains(&s.get_kind()))
+            .collect();
+        parameters.sort_by_key(|s| s.get_definition_range().start);
+
+        let mut lines = vec![];
+        if !parameters.is_empty() {
+            lines.push("**Parameters**".to_string());
+            for parameter in parameters {
+                let direction = parameter
+                    .get_direction()
+                    .map(|direction| format!("{} ", direction))
+                    .unwrap_or_default();
+                let type_name = parameter.get_type_name().unwrap_or("?");
+                lines.push(format!(
+                    "- `{}{} {}`{}",
+                    direction,
+                    compact_spaces(type_name),
+                    parameter.get_name(),
+                    self.render_resolved(type_name)
+                ));
+            }
+        }
+
+        if let Some(type_name) = symbol.get_type_name() {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!(
+                "**Returns** `{}`{}",
+                compact_spaces(type_name),
+                self.render_resolved(type_name)
+            ));
+        }
+
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// Fields of a header, with their position in it, or of a struct or union.
+    fn render_fields(&self, symbol: &Symbol) -> Option<String> {
+        let declaration = symbol.get_type_declaration()?;
+        if !matches!(
+            declaration,
+            TypeDecType::HeaderType | TypeDecType::HeaderUnion | TypeDecType::Struct
+        ) {
+            return None;
+        }
+
+        let mut fields = self
+            .symbol_table
+            .get_symbols_in_scope(symbol.get_field_scope_id()?);
+        fields.sort_by_key(|s| s.get_definition_range().start);
+        if fields.is_empty() {
+            return None;
+        }
+
+        if declaration != TypeDecType::HeaderType {
+            let mut lines = vec!["**Fields**".to_string()];
+            for field in fields {
+                let type_name = field.get_type_name().unwrap_or("?");
+                lines.push(format!(
+                    "- `{}`: `{}`{}",
+                    field.get_name(),
+                    compact_spaces(type_name),
+                    self.render_resolved(type_name)
+                ));
+            }
+            return Some(lines.join("\n"));
+        }
+
+        let mut lines = vec![
+            "| Bits | Field | Type |".to_string(),
+            "|---|---|---|".to_string(),
+        ];
+        let mut offset = Some(0);
+        for field in fields {
+            let type_name = field.get_type_name().unwrap_or("?");
+            let width = self.resolve(type_name).and_then(|t| t.width());
+            let bits = match (offset, width) {
+                (Some(offset), Some(width)) => format!("{}..{}", offset, offset + width - 1),
+                _ => "?".to_string(),
+            };
+            offset = offset.zip(width).map(|(offset, width)| offset + width);
+
+            lines.push(format!(
+                "| {} | `{}` | `{}`{} |",
+                bits,
+                field.get_name(),
+                compact_spaces(type_name),
+                self.render_resolved(type_name)
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(match offset {
+            Some(bits) if bits % 8 == 0 => format!(
+                "**Size**: {} byte{} ({} bits)",
+                bits / 8,
+                if bits == 8 { "" } else { "s" },
+                bits
+            ),
+            Some(bits) => format!("**Size**: {} bits, not a whole number of bytes", bits),
+            None => "**Size**: variable".to_string(),
+        });
+
+        Some(lines.join("\n"))
+    }
+
+    /// The type a type name stands for, when it differs from the name, e.g. ` = bit<48>`.
+    fn render_resolved(&self, type_name: &str) -> String {
+        match self.resolve(type_name) {
+            Some(resolved) if resolved.to_string() != compact_spaces(type_name) => {
+                format!(" = `{}`", resolved)
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn resolve(&self, type_name: &str) -> Option<Type> {
+        let resolved = self
+            .type_checker
+            .as_ref()?
+            .resolve_type(type_name, self.position);
+
+        (resolved != Type::Unknown).then_some(resolved)
+    }
+
+    fn is_callable(&self, symbol: &Symbol) -> bool {
+        self.details
+            .is_some_and(|details| details.callables.contains(&symbol.get_kind()))
+    }
+}
+
+/// Keys and actions of a table declaration, if it has any.
+fn render_table(declaration: VisitNode, table: &TableDetails) -> Option<String> {
+    let child_content = |node: &VisitNode, name: &str| {
+        node.get_child_of_kind(NodeKind::Node(name.to_string()))
+            .map(|child| compact_spaces(&child.get().content))
+    };
+    let is_rule =
+        |node: &VisitNode, name: &str| node.get().kind == NodeKind::Node(name.to_string());
+
+    let descendants = declaration.get_descendants();
+    let keys: Vec<String> = descendants
+        .iter()
+        .filter(|node| is_rule(node, &table.key))
+        .filter_map(|key| {
+            let expression = child_content(key, &table.key_expression)?;
+            Some(match child_content(key, &table.match_kind) {
+                Some(match_kind) => format!("- `{}`: `{}`", expression, match_kind),
+                None => format!("- `{}`", expression),
+            })
+        })
+        .collect();
+    // Constant entries also name actions, only those of the list are the table's.
+    let actions: Vec<String> = descendants
+        .iter()
+        .filter(|node| is_rule(node, &table.actions))
+        .flat_map(|list| list.get_descendants())
+        .filter(|node| is_rule(node, &table.action))
+        .map(|action| format!("- `{}`", compact_spaces(&action.get().content)))
+        .collect();
+
+    if keys.is_empty() && actions.is_empty() {
+        return None;
+    }
+
+    let mut sections = vec![];
+    if !keys.is_empty() {
+        sections.push(format!("**Keys**\n{}", keys.join("\n")));
+    }
+    if !actions.is_empty() {
+        sections.push(format!("**Actions**\n{}", actions.join("\n")));
+    }
+
+    Some(sections.join("\n\n"))
+}
+
+/// A type name, followed by the type it stands for and its width when known.
+fn describe_type(type_name: &str, resolved: &Type) -> String {
+    let type_name = compact_spaces(type_name);
+    let mut description = format!("`{}`", type_name);
+
+    if *resolved != Type::Unknown && resolved.to_string() != type_name {
+        description.push_str(&format!(" = `{}`", resolved));
+    }
+    match resolved.width() {
+        Some(width) if *resolved != Type::Bool => {
+            description.push_str(&format!(
+                " ({} bit{})",
+                width,
+                if width == 1 { "" } else { "s" }
+            ));
+        }
+        _ => {}
+    }
+
+    description
+}
+
+/// Removes the indentation common to the lines after the first one, which
+/// starts at the declaration itself.
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .reduce(common_prefix)
+        .unwrap_or("");
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| match line.strip_prefix(indent) {
+            Some(rest) if i > 0 => rest.trim_end(),
+            _ => line.trim_end(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Longest common start of two strings, ending on a character boundary of both.
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .chars()
+        .zip(b.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    &a[..len]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tower_lsp::lsp_types::{HoverContents, Url};
+
+    use super::{dedent, get_hover_info};
+    use crate::file::tests::new_parser;
+    use crate::line_index::LineIndex;
+    use crate::metadata::{AstManager, Imports, SymbolTableManager};
+
+    const SOURCE: &str = "typedef bit<48> macAddr_t;
+
+header eth_t {
+    macAddr_t dst;
+    bit<16> etherType;
+}
+
+struct headers_t {
+    eth_t eth;
+}
+
+control C(inout headers_t hdr, inout bit<9> egress) {
+    macAddr_t last;
+
+    action set_port(in bit<9> port) {
+        egress = port;
+    }
+
+    table forward {
+        key = {
+            hdr.eth.dst: exact;
+        }
+        actions = {
+            set_port;
+        }
+    }
+
+    apply {
+        forward.apply();
+    }
+}
+";
+
+    /// Markdown shown when hovering the first occurrence of `text`.
+    fn hover(text: &str) -> String {
+        let mut parser = new_parser();
+        let tree = parser.parse(SOURCE, None).unwrap();
+        let uri = Url::parse("file:///test.p4").unwrap();
+
+        let mut ast_manager = AstManager::new(SOURCE, tree);
+        let st_manager = SymbolTableManager::new(uri, ast_manager.get_ast(), &Imports::default());
+        let position = LineIndex::new(SOURCE).position(SOURCE, SOURCE.find(text).unwrap());
+
+        match get_hover_info(
+            &Arc::new(Mutex::new(ast_manager)),
+            &Arc::new(Mutex::new(st_manager)),
+            position,
+        ) {
+            Some(HoverContents::Markup(content)) => content.value,
+            other => panic!("Unexpected hover for `{}`: {:?}", text, other),
+        }
+    }
+
+    #[test]
+    fn test_hover() {
+        let header = hover("eth_t {");
+        assert!(header.contains("| 0..47 | `dst` | `macAddr_t` = `bit<48>` |"));
+        assert!(header.contains("| 48..63 | `etherType` | `bit<16>` |"));
+        assert!(header.contains("**Size**: 8 bytes (64 bits)"));
+
+        assert!(hover("last;").contains("`last`: `macAddr_t` = `bit<48>` (48 bits)"));
+        assert!(hover("macAddr_t;").contains("`macAddr_t` = `bit<48>` (48 bits)"));
+
+        let action = hover("set_port;");
+        assert!(action.starts_with("```p4\naction set_port(in bit<9> port) { ... }\n```"));
+        assert!(action.contains("- `in bit<9> port`"));
+
+        let control = hover("C(");
+        assert!(control.contains("- `inout headers_t hdr`"));
+        assert!(control.contains("- `inout bit<9> egress`"));
 
-    let type_symbol = st_query.get_symbol(symbol.get_type_symbol()?)?;
+        let table = hover("forward.apply");
+        assert!(table.contains("**Keys**\n- `hdr.eth.dst`: `exact`"));
+        assert!(table.contains("**Actions**\n- `set_port`"));
+    }
 
-    Some(HoverContents::Scalar(MarkedString::String(format!(
-        "{}: {}",
-        symbol.get_name(),
-        type_symbol.get_name()
-    ))))
+    #[test]
+    fn test_dedent_multibyte_whitespace() {
+        let lines = ["action a() {", "\u{3000}\u{3000}x = 1;", "", "\u{3000}}"];
+        assert_eq!(dedent(&lines), "action a() {\n\u{3000}x = 1;\n\n}");
+        assert_eq!(dedent(&["f(", "\t a,", "    b)"]), "f(\n\t a,\n    b)");
+    }
 }
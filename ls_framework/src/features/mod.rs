@@ -1,6 +1,10 @@
 pub mod completion;
 pub mod diagnostics;
+pub mod document_symbols;
 pub mod goto;
 pub mod hover;
+pub mod references;
 pub mod rename;
 pub mod semantic_tokens;
+pub mod signature_help;
+pub mod workspace_symbols;
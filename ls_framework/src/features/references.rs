@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{Location, Range, Url};
+
+pub fn build_locations(
+    definition: Location,
+    usages: HashMap<Url, Vec<Range>>,
+    include_declaration: bool,
+) -> Vec<Location> {
+    let mut locations: Vec<Location> = usages
+        .into_iter()
+        .flat_map(|(uri, ranges)| {
+            ranges
+                .into_iter()
+                .map(move |range| Location::new(uri.clone(), range))
+        })
+        .collect();
+    locations.sort_by(|a, b| (&a.uri, a.range.start).cmp(&(&b.uri, b.range.start)));
+
+    if include_declaration {
+        locations.insert(0, definition);
+    }
+
+    locations
+}
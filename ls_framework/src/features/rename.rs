@@ -3,37 +3,45 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use crate::metadata::{AstQuery, Symbol, SymbolTableQuery, Visitable};
-use tower_lsp::lsp_types::{Position, TextEdit, Url, WorkspaceEdit};
+use crate::metadata::SymbolTableQuery;
+use tower_lsp::lsp_types::{Location, Range, TextEdit, Url, WorkspaceEdit};
 
-pub fn rename(
-    ast_query: &Arc<Mutex<impl AstQuery>>,
+pub fn get_usages(
     symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
-    uri: Url,
-    new_name: String,
-    position: Position,
-) -> Option<WorkspaceEdit> {
-    let ast_query = ast_query.lock().unwrap();
-    let root_visit = ast_query.visit_root();
-    let node = root_visit.get_node_at_position(position)?;
-
+    definition: &Location,
+) -> Vec<Range> {
     let symbol_table_query = symbol_table_query.lock().unwrap();
-    let symbol = symbol_table_query.get_symbol(node.get().linked_symbol.clone()?)?;
 
-    Some(WorkspaceEdit::new(build_changes(uri, symbol, new_name)))
+    let mut usages: Vec<Range> = symbol_table_query
+        .get_symbols_defined_at(definition)
+        .iter()
+        .flat_map(|symbol| symbol.get_usages().clone())
+        .collect();
+    usages.sort_by_key(|range| range.start);
+    usages.dedup();
+
+    usages
 }
 
-fn build_changes(uri: Url, symbol: &Symbol, new_name: String) -> HashMap<Url, Vec<TextEdit>> {
-    let mut edits: Vec<TextEdit> = Vec::new();
+pub fn build_changes(
+    definition: Location,
+    usages: HashMap<Url, Vec<Range>>,
+    new_name: String,
+) -> WorkspaceEdit {
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+
+    changes
+        .entry(definition.uri)
+        .or_default()
+        .push(TextEdit::new(definition.range, new_name.clone()));
 
-    edits.push(TextEdit::new(
-        symbol.get_definition_range(),
-        new_name.clone(),
-    ));
+    for (uri, ranges) in usages {
+        let edits = changes.entry(uri).or_default();
 
-    for range in symbol.get_usages() {
-        edits.push(TextEdit::new(*range, new_name.clone()));
+        for range in ranges {
+            edits.push(TextEdit::new(range, new_name.clone()));
+        }
     }
 
-    HashMap::from([(uri, edits)])
+    WorkspaceEdit::new(changes)
 }
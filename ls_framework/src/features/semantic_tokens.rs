@@ -1,94 +1,244 @@
 use crate::{
-    language_def::{self, LanguageDefinition},
-    lsp_mappings::HighlightType,
-    metadata::{AstQuery, SymbolTableQuery, Visitable},
+    language_def::{self, LanguageDefinition, ModifierCondition, SymbolDef},
+    line_index::LineIndex,
+    metadata::{AstQuery, Symbol, SymbolTableQuery, VisitNode, Visitable},
     utils,
 };
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
-use tower_lsp::lsp_types::{SemanticToken, SemanticTokens, SemanticTokensResult};
+use tower_lsp::lsp_types::{Range, SemanticToken, SemanticTokensEdit};
 use tree_sitter::Node;
 
+static NEXT_RESULT_ID: AtomicU64 = AtomicU64::new(1);
+
 pub struct ColorData {
+    range: Range,
+    node_type: u32,
+    modifiers: u32,
+}
+
+/// Origin of a token. Where tokens overlap, the one from the later source wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TokenSource {
+    Keyword,
+    Ast,
+    Symbol,
+}
+
+/// Single line part of a token, from `start` to `end` in the units of the position encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LineToken {
     line: u32,
     start: u32,
-    length: u32,
+    end: u32,
     node_type: u32,
+    modifiers: u32,
+}
+
+/// Gathers the tokens of a document from all sources and encodes them as the
+/// LSP expects: on a single line, without overlaps, ordered by position.
+struct TokenCollector<'a> {
+    source_code: &'a str,
+    line_index: LineIndex,
+    tokens: Vec<(TokenSource, ColorData)>,
 }
 
+impl<'a> TokenCollector<'a> {
+    fn new(source_code: &'a str) -> TokenCollector<'a> {
+        TokenCollector {
+            source_code,
+            line_index: LineIndex::new(source_code),
+            tokens: vec![],
+        }
+    }
+
+    fn add(&mut self, source: TokenSource, color_data: Vec<ColorData>) {
+        self.tokens
+            .extend(color_data.into_iter().map(|data| (source, data)));
+    }
+
+    /// Splits the tokens spanning several lines into one token per line.
+    fn split_lines(&self, data: &ColorData) -> Vec<LineToken> {
+        let Range { start, end } = data.range;
+
+        (start.line..=end.line)
+            .map(|line| LineToken {
+                line,
+                start: if line == start.line {
+                    start.character
+                } else {
+                    0
+                },
+                end: if line == end.line {
+                    end.character
+                } else {
+                    self.line_index.line_length(self.source_code, line)
+                },
+                node_type: data.node_type,
+                modifiers: data.modifiers,
+            })
+            .filter(|token| token.start < token.end)
+            .collect()
+    }
+
+    /// Resolves the overlaps: each character keeps the token of the source
+    /// with the highest priority, or the first one added among equal sources.
+    fn resolve(&self) -> Vec<LineToken> {
+        let mut by_line: BTreeMap<u32, Vec<(TokenSource, LineToken)>> = BTreeMap::new();
+        for (source, data) in &self.tokens {
+            for token in self.split_lines(data) {
+                by_line
+                    .entry(token.line)
+                    .or_default()
+                    .push((*source, token));
+            }
+        }
+
+        let mut resolved = vec![];
+        for (_, mut line_tokens) in by_line {
+            line_tokens.sort_by_key(|(source, _)| std::cmp::Reverse(*source));
+
+            let mut taken: Vec<(u32, u32)> = vec![];
+            for (_, token) in line_tokens {
+                for (start, end) in free_parts(token.start, token.end, &taken) {
+                    taken.push((start, end));
+                    resolved.push(LineToken {
+                        start,
+                        end,
+                        ..token
+                    });
+                }
+            }
+        }
+
+        resolved.sort_by_key(|token| (token.line, token.start));
+        resolved
+    }
+
+    /// Encodes the tokens on the lines of `range`, or all of them, relative to the previous one.
+    fn encode(&self, range: Option<Range>) -> Vec<SemanticToken> {
+        let mut tokens = self.resolve();
+        tokens.retain(|token| is_on_lines(range, token.line, token.line));
+
+        let (mut prev_line, mut prev_start) = (0, 0);
+        tokens
+            .into_iter()
+            .map(|token| {
+                let delta_line = token.line - prev_line;
+                let delta_start = if delta_line == 0 {
+                    token.start - prev_start
+                } else {
+                    token.start
+                };
+                (prev_line, prev_start) = (token.line, token.start);
+
+                SemanticToken {
+                    delta_line,
+                    delta_start,
+                    length: token.end - token.start,
+                    token_type: token.node_type,
+                    token_modifiers_bitset: token.modifiers,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Parts of `start..end` not covered by the `taken` intervals.
+fn free_parts(start: u32, end: u32, taken: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut taken = taken.to_vec();
+    taken.sort();
+
+    let mut parts = vec![];
+    let mut cursor = start;
+    for (taken_start, taken_end) in taken {
+        if taken_start >= end {
+            break;
+        }
+        if taken_start > cursor {
+            parts.push((cursor, taken_start));
+        }
+        cursor = cursor.max(taken_end);
+    }
+    if cursor < end {
+        parts.push((cursor, end));
+    }
+
+    parts
+}
+
+/// Whether the lines from `start` to `end` have one in common with the lines
+/// of `range`. Every line is in a missing range.
+fn is_on_lines(range: Option<Range>, start: u32, end: u32) -> bool {
+    range.is_none_or(|range| start <= range.end.line && range.start.line <= end)
+}
+
+/// Computes the tokens of a document, only looking at the lines of `range` if given.
+/// Symbols defined in one of the `library_files` are library symbols.
 pub fn get_tokens(
     ast_query: &Arc<Mutex<impl AstQuery>>,
     st_query: &Arc<Mutex<impl SymbolTableQuery>>,
     ts_tree: &tree_sitter::Tree,
     source_code: &str,
-) -> SemanticTokensResult {
-    //Getting ast data
-    let mut array = get_keyword_color_data(&ts_tree.root_node(), source_code);
-    array.append(&mut get_symbols_color_data(st_query));
-    array.append(&mut get_ast_color_data(ast_query));
-    //sort line
-
-    array.sort_by_key(|token| token.line);
-
-    let mut prev = 0;
-    let mut tokens: Vec<SemanticToken> = vec![];
-
-    let mut temp_array: Vec<SemanticToken> = Vec::new();
-    for item in array {
-        //Calculating the line diff between current and previous line
-        let line = ((item.line - prev) as i32).abs();
-
-        prev = item.line;
-        //If line is greater than 0 then we save the temp array of tokens
-        if line > 0 && !temp_array.is_empty() {
-            let mut max_val = 0;
-            temp_array.sort_by_key(|&token| token.delta_start); //sorting the start pos
-                                                                //setting the first deltaline to conatin the diff value (line) and setting all other to 0
-            for item in &mut temp_array {
-                if item.delta_line > max_val {
-                    max_val = item.delta_line;
-                }
-                item.delta_line = 0;
-            }
-            temp_array[0].delta_line = max_val;
+    range: Option<Range>,
+    library_files: &[PathBuf],
+) -> Vec<SemanticToken> {
+    let mut collector = TokenCollector::new(source_code);
+    let keywords = get_keyword_color_data(
+        &ts_tree.root_node(),
+        source_code,
+        &collector.line_index,
+        range,
+    );
 
-            //ReCalculating the delta start relative to the previous start pos
-            let mut prev_start = 0;
-            temp_array = temp_array
-                .iter()
-                .map(|token| {
-                    let temp_token = SemanticToken {
-                        delta_line: token.delta_line,
-                        delta_start: token.delta_start - prev_start,
-                        length: token.length,
-                        token_type: token.token_type,
-                        token_modifiers_bitset: 0,
-                    };
-
-                    prev_start = token.delta_start;
-                    temp_token
-                })
-                .collect();
+    collector.add(TokenSource::Keyword, keywords);
+    collector.add(TokenSource::Ast, get_ast_color_data(ast_query, range));
+    collector.add(
+        TokenSource::Symbol,
+        get_symbols_color_data(st_query, library_files, range),
+    );
 
-            tokens.extend(temp_array); //concating two vectors
-            temp_array = Vec::new();
-        }
+    collector.encode(range)
+}
 
-        temp_array.push(SemanticToken {
-            delta_line: line as u32,
-            delta_start: item.start,
-            length: item.length,
-            token_type: item.node_type,
-            token_modifiers_bitset: 0,
-        });
+/// Returns a new identifier for a version of the tokens of a document.
+pub fn new_result_id() -> String {
+    NEXT_RESULT_ID.fetch_add(1, Ordering::Relaxed).to_string()
+}
+
+/// Returns the edits turning the `previous` tokens into the `current` ones:
+/// everything between their common prefix and suffix is replaced.
+pub fn get_edits(previous: &[SemanticToken], current: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(previous, current)| previous == current)
+        .count();
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(previous, current)| previous == current)
+        .count();
+
+    let deleted = previous.len() - prefix - suffix;
+    let inserted = &current[prefix..current.len() - suffix];
+    if deleted == 0 && inserted.is_empty() {
+        return vec![];
     }
 
-    SemanticTokensResult::Tokens(SemanticTokens {
-        result_id: None,
-        data: tokens,
-    })
+    // Edits index the flattened array, where each token is 5 integers
+    vec![SemanticTokensEdit {
+        start: 5 * prefix as u32,
+        delete_count: 5 * deleted as u32,
+        data: Some(inserted.to_vec()),
+    }]
 }
 
 fn get_semantic_token_map() -> HashMap<String, usize> {
@@ -104,7 +254,12 @@ fn get_semantic_token_map() -> HashMap<String, usize> {
     semantic_token_types_map
 }
 
-pub fn get_keyword_color_data(root_node: &tree_sitter::Node, source_code: &str) -> Vec<ColorData> {
+pub fn get_keyword_color_data(
+    root_node: &tree_sitter::Node,
+    source_code: &str,
+    line_index: &LineIndex,
+    range: Option<Range>,
+) -> Vec<ColorData> {
     let keywords = crate::language_def::LanguageDefinition::get_keywords();
 
     let mut cursor = root_node.walk();
@@ -113,12 +268,16 @@ pub fn get_keyword_color_data(root_node: &tree_sitter::Node, source_code: &str)
     let mut color_data = vec![];
 
     while let Some(node) = to_visit.pop() {
+        let (start, end) = (node.start_position().row, node.end_position().row);
+        if !is_on_lines(range, start as u32, end as u32) {
+            continue;
+        }
+
         if !node.is_named() && keywords.contains(&utils::get_node_text(&node, source_code)) {
             color_data.push(ColorData {
-                length: (node.range().end_byte - node.range().start_byte) as u32,
-                start: node.range().start_point.column as u32,
-                line: node.range().start_point.row as u32,
+                range: line_index.range(source_code, node.range()),
                 node_type: 0,
+                modifiers: 0,
             });
         } else {
             to_visit.append(&mut node.children(&mut cursor).collect::<Vec<Node>>());
@@ -128,32 +287,54 @@ pub fn get_keyword_color_data(root_node: &tree_sitter::Node, source_code: &str)
     color_data
 }
 
-pub fn get_symbols_color_data(st_query: &Arc<Mutex<impl SymbolTableQuery>>) -> Vec<ColorData> {
+pub fn get_symbols_color_data(
+    st_query: &Arc<Mutex<impl SymbolTableQuery>>,
+    library_files: &[PathBuf],
+    range: Option<Range>,
+) -> Vec<ColorData> {
     let semantic_token_types_map = get_semantic_token_map();
 
-    let symbols = st_query.lock().unwrap().get_all_symbols();
+    let (symbols, imported_symbols) = {
+        let st_query = st_query.lock().unwrap();
+        (st_query.get_all_symbols(), st_query.get_imported_symbols())
+    };
 
     let mut color_data = vec![];
-    for symbol in symbols {
-        let highlight_type = get_symbol_highlight_type(symbol.get_kind());
+    // Imported symbols are defined elsewhere, only their usages are in this file
+    let definitions = symbols.iter().map(|symbol| (symbol, true));
+    for (symbol, is_local) in
+        definitions.chain(imported_symbols.iter().map(|symbol| (symbol, false)))
+    {
+        let in_range =
+            |occurrence: &Range| is_on_lines(range, occurrence.start.line, occurrence.end.line);
+        let is_definition_in_range =
+            is_local && !symbol.is_builtin() && in_range(&symbol.get_definition_range());
+        let usages: Vec<&Range> = symbol.get_usages().iter().filter(|r| in_range(r)).collect();
+        if !is_definition_in_range && usages.is_empty() {
+            continue;
+        }
+
+        let symbol_def = LanguageDefinition::get()
+            .get_symbol_def(&symbol.get_kind())
+            .unwrap();
         let node_type = *semantic_token_types_map
-            .get(highlight_type.get().as_str())
+            .get(symbol_def.highlight_type.get().as_str())
             .unwrap() as u32;
 
-        let def_range = symbol.get_definition_range();
-        color_data.push(ColorData {
-            line: def_range.start.line,
-            start: def_range.start.character,
-            length: def_range.end.character - def_range.start.character,
-            node_type,
-        });
+        let modifiers = get_modifiers_bitset(symbol_def, symbol, false, library_files);
+        if is_definition_in_range {
+            color_data.push(ColorData {
+                range: symbol.get_definition_range(),
+                node_type,
+                modifiers: get_modifiers_bitset(symbol_def, symbol, true, library_files),
+            });
+        }
 
-        for range in symbol.get_usages() {
+        for usage in usages {
             color_data.push(ColorData {
-                line: range.start.line,
-                start: range.start.character,
-                length: range.end.character - range.start.character,
+                range: *usage,
                 node_type,
+                modifiers,
             });
         }
     }
@@ -161,36 +342,384 @@ pub fn get_symbols_color_data(st_query: &Arc<Mutex<impl SymbolTableQuery>>) -> V
     color_data
 }
 
-fn get_symbol_highlight_type(symbol_kind: String) -> HighlightType {
-    LanguageDefinition::get()
-        .symbol_types
+/// Returns the modifiers of an occurrence of a symbol as a bitset of the legend's modifiers.
+fn get_modifiers_bitset(
+    symbol_def: &SymbolDef,
+    symbol: &Symbol,
+    is_definition: bool,
+    library_files: &[PathBuf],
+) -> u32 {
+    let is_library = symbol
+        .get_definition_location()
+        .uri
+        .to_file_path()
+        .is_ok_and(|path| library_files.contains(&path));
+
+    let occurrence_modifiers = symbol_def
+        .highlight_modifiers
         .iter()
-        .find(|symbol_type| symbol_type.name == symbol_kind)
-        .unwrap()
-        .highlight_type
-        .clone()
+        .filter(|(_, condition)| match condition {
+            ModifierCondition::Definition => is_definition,
+            ModifierCondition::Library => is_library,
+            ModifierCondition::Always | ModifierCondition::Child(..) => false,
+        })
+        .map(|(modifier, _)| modifier);
+
+    let legend = LanguageDefinition::get_semantic_token_modifiers();
+    occurrence_modifiers
+        .chain(symbol.get_declaration_modifiers())
+        .filter_map(|modifier| legend.iter().position(|m| *m == modifier.get()))
+        .fold(0, |bitset, index| bitset | 1 << index)
 }
 
-pub fn get_ast_color_data(ast_query: &Arc<Mutex<impl AstQuery>>) -> Vec<ColorData> {
+pub fn get_ast_color_data(
+    ast_query: &Arc<Mutex<impl AstQuery>>,
+    range: Option<Range>,
+) -> Vec<ColorData> {
     let semantic_token_types_map = get_semantic_token_map();
 
     let ast_query = ast_query.lock().unwrap();
 
     let mut color_data = vec![];
-    for visit_node in ast_query.visit_root().get_descendants() {
-        let node = visit_node.get();
+    for child in ast_query.visit_root().get_children() {
+        collect_ast_color_data(&child, range, &semantic_token_types_map, &mut color_data);
+    }
 
-        if let Some(semantic_token_type) = &node.semantic_token_type {
-            color_data.push(ColorData {
-                line: node.range.start.line,
-                start: node.range.start.character,
-                length: node.range.end.character - node.range.start.character,
-                node_type: *semantic_token_types_map
-                    .get(semantic_token_type.get().as_str())
-                    .unwrap() as u32,
+    color_data
+}
+
+/// Adds the tokens of a node and of its descendants. Nodes are within their
+/// parent, so the subtrees outside of `range` are skipped.
+fn collect_ast_color_data(
+    visit_node: &VisitNode,
+    range: Option<Range>,
+    semantic_token_types_map: &HashMap<String, usize>,
+    color_data: &mut Vec<ColorData>,
+) {
+    let node = visit_node.get();
+    if !is_on_lines(range, node.range.start.line, node.range.end.line) {
+        return;
+    }
+
+    if let Some(semantic_token_type) = &node.semantic_token_type {
+        color_data.push(ColorData {
+            range: node.range,
+            node_type: *semantic_token_types_map
+                .get(semantic_token_type.get().as_str())
+                .unwrap() as u32,
+            modifiers: 0,
+        });
+    }
+
+    for child in visit_node.get_children() {
+        collect_ast_color_data(&child, range, semantic_token_types_map, color_data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{Position, Range, SemanticToken, SemanticTokensEdit, Url};
+
+    use super::{get_edits, ColorData, TokenCollector, TokenSource};
+    use crate::file::{tests::new_parser, File};
+    use crate::language_def::LanguageDefinition;
+    use crate::line_index::LineIndex;
+    use crate::metadata::Imports;
+
+    fn data(start: (u32, u32), end: (u32, u32), node_type: u32) -> ColorData {
+        ColorData {
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            node_type,
+            modifiers: 0,
+        }
+    }
+
+    fn encoded(delta_line: u32, delta_start: u32, length: u32, token_type: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    #[test]
+    fn test_encode_orders_tokens() {
+        let mut collector = TokenCollector::new("a bb c\nd\n\neee");
+        collector.add(
+            TokenSource::Symbol,
+            vec![data((3, 0), (3, 3), 1), data((0, 5), (0, 6), 1)],
+        );
+        collector.add(
+            TokenSource::Keyword,
+            vec![
+                data((0, 2), (0, 4), 0),
+                data((1, 0), (1, 1), 0),
+                data((0, 0), (0, 1), 0),
+            ],
+        );
+
+        assert_eq!(
+            collector.encode(None),
+            vec![
+                encoded(0, 0, 1, 0),
+                encoded(0, 2, 2, 0),
+                encoded(0, 3, 1, 1),
+                encoded(1, 0, 1, 0),
+                encoded(2, 0, 3, 1),
+            ]
+        );
+        assert_eq!(
+            collector.encode(Some(Range::new(Position::new(1, 0), Position::new(2, 0)))),
+            vec![encoded(1, 0, 1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_encode_resolves_overlaps() {
+        let mut collector = TokenCollector::new("int x = y; // z");
+        collector.add(TokenSource::Keyword, vec![data((0, 0), (0, 3), 0)]);
+        collector.add(
+            TokenSource::Ast,
+            vec![data((0, 0), (0, 3), 2), data((0, 11), (0, 15), 3)],
+        );
+        collector.add(
+            TokenSource::Symbol,
+            vec![
+                data((0, 4), (0, 5), 1),
+                data((0, 4), (0, 5), 4),
+                data((0, 14), (0, 15), 1),
+            ],
+        );
+
+        assert_eq!(
+            collector.encode(None),
+            vec![
+                encoded(0, 0, 3, 2),
+                encoded(0, 4, 1, 1),
+                encoded(0, 7, 3, 3),
+                encoded(0, 3, 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_splits_multi_line_tokens() {
+        let mut collector = TokenCollector::new("x /* é\r\n\r\n  😀 */ y");
+        collector.add(TokenSource::Ast, vec![data((0, 2), (2, 7), 3)]);
+        collector.add(TokenSource::Symbol, vec![data((2, 8), (2, 9), 1)]);
+
+        assert_eq!(
+            collector.encode(None),
+            vec![
+                encoded(0, 2, 4, 3),
+                encoded(2, 0, 7, 3),
+                encoded(0, 8, 1, 1)
+            ]
+        );
+    }
+
+    /// Renders the tokens of a file one per line, with their position, type,
+    /// modifiers and text, so the golden files can be reviewed.
+    fn render(source: &str, tokens: &[SemanticToken]) -> String {
+        let types = LanguageDefinition::get_semantic_token_types();
+        let modifiers = LanguageDefinition::get_semantic_token_modifiers();
+        let line_index = LineIndex::new(source);
+
+        let mut output = String::new();
+        let (mut line, mut start) = (0, 0);
+        for token in tokens {
+            line += token.delta_line;
+            start = if token.delta_line == 0 {
+                start + token.delta_start
+            } else {
+                token.delta_start
+            };
+
+            let text = &source[line_index.offset(source, Position::new(line, start))
+                ..line_index.offset(source, Position::new(line, start + token.length))];
+            let token_modifiers: Vec<&str> = modifiers
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| token.token_modifiers_bitset & 1 << i != 0)
+                .map(|(_, modifier)| modifier.as_str())
+                .collect();
+
+            output.push_str(&format!(
+                "{}:{} {} [{}] {:?}\n",
+                line,
+                start,
+                types[token.token_type as usize].as_str(),
+                token_modifiers.join(", "),
+                text
+            ));
+        }
+
+        output
+    }
+
+    /// Compares the tokens of each example with its golden file under `examples/golden`.
+    /// The golden files are only written when `UPDATE_GOLDEN` is set.
+    #[test]
+    #[ignore = "no golden file is committed yet, write them with `UPDATE_GOLDEN=1 cargo test -- --ignored test_golden_examples`"]
+    fn test_golden_examples() {
+        let mut parser = new_parser();
+
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        let mut paths: Vec<_> = std::fs::read_dir(&examples)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "p4"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let source = std::fs::read_to_string(&path).unwrap();
+            let tree = parser.parse(&source, None);
+            let uri = Url::from_file_path(path.canonicalize().unwrap()).unwrap();
+            let file = File::new(uri, &source, &tree, vec![], &Imports::default());
+            let rendered = render(&source, &file.get_semantic_tokens(&[]).unwrap().data);
+
+            let golden = examples
+                .join("golden")
+                .join(path.file_name().unwrap())
+                .with_extension("tokens");
+            if std::env::var_os("UPDATE_GOLDEN").is_some() {
+                std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+                std::fs::write(&golden, &rendered).unwrap();
+                continue;
+            }
+
+            let expected = std::fs::read_to_string(&golden).unwrap_or_else(|e| {
+                panic!(
+                    "missing golden file {} ({}), run the test with UPDATE_GOLDEN=1 to write it",
+                    golden.display(),
+                    e
+                )
             });
+            assert_eq!(
+                rendered,
+                expected,
+                "tokens of {} differ from {}",
+                path.display(),
+                golden.display()
+            );
         }
     }
 
-    color_data
+    /// The tokens of a range, computed from the nodes and symbols on its lines
+    /// only, are the tokens of the whole file on these lines.
+    #[test]
+    fn test_range_matches_full_tokens() {
+        let mut parser = new_parser();
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/basic.p4");
+        let source = std::fs::read_to_string(&path).unwrap();
+        let tree = parser.parse(&source, None);
+        let uri = Url::from_file_path(path.canonicalize().unwrap()).unwrap();
+        let file = File::new(uri, &source, &tree, vec![], &Imports::default());
+
+        let full = render(&source, &file.get_semantic_tokens(&[]).unwrap().data);
+        let line_count = source.lines().count() as u32;
+        let (first, last) = (line_count / 3, 2 * line_count / 3);
+        let expected: String = full
+            .lines()
+            .filter(|token| {
+                let line: u32 = token.split(':').next().unwrap().parse().unwrap();
+                first <= line && line <= last
+            })
+            .map(|token| format!("{}\n", token))
+            .collect();
+
+        let range = Range::new(Position::new(first, 0), Position::new(last, 0));
+        let tokens = file.get_semantic_tokens_range(range, &[]).unwrap().data;
+        assert!(!expected.is_empty());
+        assert_eq!(render(&source, &tokens), expected);
+    }
+
+    /// A call takes the modifiers of the overload with as many parameters as it
+    /// has arguments, not of the first declaration with the name.
+    #[test]
+    fn test_usage_modifiers_follow_overload() {
+        let source = "@deprecated(\"Use f(x) instead.\")
+extern bit<8> f();
+extern bit<8> f(in bit<8> x);
+
+control C(inout bit<8> y) {
+    apply {
+        y = f(y);
+        y = f();
+    }
+}
+";
+        let mut parser = new_parser();
+        let tree = parser.parse(source, None);
+        let uri = Url::parse("file:///test.p4").unwrap();
+        let file = File::new(uri, source, &tree, vec![], &Imports::default());
+
+        let rendered = render(source, &file.get_semantic_tokens(&[]).unwrap().data);
+        let call = |line: &str| {
+            rendered
+                .lines()
+                .find(|token| token.starts_with(line) && token.ends_with("\"f\""))
+                .unwrap()
+                .to_string()
+        };
+        assert!(!call("6:").contains("deprecated"));
+        assert!(call("7:").contains("deprecated"));
+    }
+
+    fn token(delta_line: u32, delta_start: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length: 3,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    #[test]
+    fn test_get_edits() {
+        let previous = vec![token(0, 0), token(1, 2), token(0, 4), token(2, 0)];
+
+        assert_eq!(get_edits(&previous, &previous), vec![]);
+        assert_eq!(
+            get_edits(&previous, &[token(0, 0), token(1, 3), token(2, 0)]),
+            vec![SemanticTokensEdit {
+                start: 5,
+                delete_count: 10,
+                data: Some(vec![token(1, 3)]),
+            }]
+        );
+        assert_eq!(
+            get_edits(
+                &previous,
+                &[
+                    token(0, 0),
+                    token(1, 2),
+                    token(0, 4),
+                    token(2, 0),
+                    token(0, 6)
+                ]
+            ),
+            vec![SemanticTokensEdit {
+                start: 20,
+                delete_count: 0,
+                data: Some(vec![token(0, 6)]),
+            }]
+        );
+        assert_eq!(
+            get_edits(&previous, &[]),
+            vec![SemanticTokensEdit {
+                start: 0,
+                delete_count: 20,
+                data: Some(vec![]),
+            }]
+        );
+    }
 }
@@ -0,0 +1,366 @@
+use std::sync::{Arc, Mutex};
+
+use tower_lsp::lsp_types::{
+    ParameterInformation, ParameterLabel, Position, SignatureHelp, SignatureInformation,
+};
+
+use crate::language_def::LanguageDefinition;
+use crate::line_index::LineIndex;
+use crate::metadata::{
+    resolve_callee, AstQuery, Call, Signature, SymbolTableQuery, VisitNode, Visitable,
+};
+
+pub fn get_signature_help(
+    ast_query: &Arc<Mutex<impl AstQuery>>,
+    symbol_table_query: &Arc<Mutex<impl SymbolTableQuery>>,
+    position: Position,
+) -> Option<SignatureHelp> {
+    let config = LanguageDefinition::get().calls.as_ref()?;
+
+    let ast_query = ast_query.lock().unwrap();
+    let st_query = symbol_table_query.lock().unwrap();
+    let root = ast_query.visit_root();
+
+    // Nested calls start after the calls containing them, the innermost one is the last
+    let (call, arguments, rest) = root
+        .get_descendants()
+        .into_iter()
+        .filter(|node| {
+            // Only the nodes around the position are worth resolving
+            let range = node.get().range;
+            range.start < position && position <= range.end
+        })
+        .filter_map(|node| {
+            let call = resolve_callee(&*st_query, config, &node)?;
+            let (arguments, rest) = get_arguments_around(&node, &call, position)?;
+            Some((call, arguments, rest))
+        })
+        .max_by_key(|(call, _, _)| call.range.start)?;
+    if call.signatures.is_empty() {
+        return None;
+    }
+
+    let index = arguments.len() - 1;
+    // The name of a named argument may be written already, e.g. `f(value| = x)`
+    let named = arguments
+        .last()
+        .and_then(|argument| get_argument_name(&format!("{}{}", argument, rest)));
+    let active = |parameters: &[String]| match &named {
+        Some(name) => parameters.iter().position(|parameter| parameter == name),
+        None => Some(index),
+    };
+
+    let signatures: Vec<SignatureInformation> = call
+        .signatures
+        .iter()
+        .map(|signature| {
+            let names: Vec<String> = signature
+                .parameters
+                .iter()
+                .map(|parameter| parameter.get_name())
+                .collect();
+            let (label, parameters) = render_signature(signature);
+            SignatureInformation {
+                label,
+                documentation: None,
+                parameters: Some(parameters),
+                active_parameter: active(&names).map(|index| index as u32),
+            }
+        })
+        .collect();
+
+    // Overloads are told apart by their number of parameters, the first one taking
+    // all the arguments given so far is shown.
+    let active_signature = call
+        .signatures
+        .iter()
+        .position(|signature| signature.parameters.len() > index || named.is_some())
+        .unwrap_or(0);
+    let active_parameter = signatures[active_signature].active_parameter;
+
+    Some(SignatureHelp {
+        signatures,
+        active_signature: Some(active_signature as u32),
+        active_parameter,
+    })
+}
+
+/// Label of a signature, with the offsets of its parameters in it.
+fn render_signature(signature: &Signature) -> (String, Vec<ParameterInformation>) {
+    let mut label = format!("{}(", signature.callable.get_name());
+    let mut parameters = vec![];
+
+    for (i, parameter) in signature.parameters.iter().enumerate() {
+        if i > 0 {
+            label.push_str(", ");
+        }
+        let start = label.encode_utf16().count() as u32;
+        label.push_str(&signature.format_parameter(parameter));
+        let end = label.encode_utf16().count() as u32;
+
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: None,
+        });
+    }
+    label.push(')');
+
+    (label, parameters)
+}
+
+/// Texts of the arguments written between the parenthesis following the callee of a
+/// call and the position, the last one being edited, with the rest of that argument
+/// after the position. `None` if the position isn't between the parenthesis.
+fn get_arguments_around(
+    node: &VisitNode,
+    call: &Call,
+    position: Position,
+) -> Option<(Vec<String>, String)> {
+    let data = node.get();
+    if position <= call.range.end || position > data.range.end {
+        return None;
+    }
+
+    let content = &data.content;
+    let line_index = LineIndex::new(content);
+    let start = data.range.start;
+    let offset = |position: Position| {
+        let relative = if position.line == start.line {
+            Position::new(0, position.character.saturating_sub(start.character))
+        } else {
+            Position::new(position.line.saturating_sub(start.line), position.character)
+        };
+        line_index.offset(content, relative)
+    };
+
+    let text = content.get(offset(call.range.end)..offset(position))?;
+    let rest = content.get(offset(position)..)?;
+    Some((split_arguments(text)?, argument_end(rest).to_string()))
+}
+
+/// Arguments written after the opening parenthesis starting a text, e.g. `a` and `b`
+/// in `(a, b`. `None` if the text doesn't start with a parenthesis or closes it.
+fn split_arguments(text: &str) -> Option<Vec<String>> {
+    // Type arguments may come before the parenthesis, e.g. `f<bit<8>>(`
+    let inner = text.trim_start();
+    let inner = match inner.strip_prefix('<') {
+        Some(_) => &inner[find_closing(inner)? + 1..],
+        None => inner,
+    };
+    let inner = inner.trim_start().strip_prefix('(')?;
+
+    let mut arguments = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth == 0 => return None,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(inner[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(inner[start..].trim().to_string());
+
+    Some(arguments)
+}
+
+/// Start of a text up to the end of the argument it is in, e.g. ` = x` in ` = x, y)`.
+fn argument_end(text: &str) -> &str {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' | ',' if depth == 0 => return &text[..i],
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    text
+}
+
+/// Offset of the `>` closing the angle bracket starting a text.
+fn find_closing(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Name of the parameter a named argument is passed to, e.g. `value` in `value = x`.
+fn get_argument_name(argument: &str) -> Option<String> {
+    let (name, rest) = argument.split_once('=')?;
+    let name = name.trim();
+    if rest.starts_with('=') || name.is_empty() {
+        return None;
+    }
+
+    name.chars()
+        .all(|c| c.is_alphanumeric() || c == '_')
+        .then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tower_lsp::lsp_types::{SignatureHelp, Url};
+
+    use super::{argument_end, get_argument_name, get_signature_help, split_arguments};
+    use crate::file::tests::new_parser;
+    use crate::line_index::LineIndex;
+    use crate::metadata::{AstManager, Imports, SymbolTableManager};
+
+    const SOURCE: &str = "extern register<T> {
+    register(bit<32> size);
+    void read(out T result, in bit<32> index);
+    void write(in bit<32> index, in T value);
+}
+
+extern packet_in {
+    void extract<H>(out H hdr);
+    void extract<H>(out H variableSizeHeader, in bit<32> variableFieldSizeInBits);
+}
+
+control Sub(inout bit<32> x) {
+    apply { }
+}
+
+control C(packet_in pkt, inout bit<32> x) {
+    register<bit<32>>(1024) r;
+    Sub() sub;
+
+    action set(in bit<32> v, bit<8> port) { }
+
+    apply {
+        r.read(x, 0);
+        r.write(value = x, index = 0);
+        pkt.extract(x, 8);
+        set(x, 1);
+        sub.apply(x);
+    }
+}
+";
+
+    /// Signature help right after the first occurrence of `text`.
+    fn signature_help(text: &str) -> SignatureHelp {
+        let mut parser = new_parser();
+        let tree = parser.parse(SOURCE, None).unwrap();
+        let uri = Url::parse("file:///test.p4").unwrap();
+
+        let mut ast_manager = AstManager::new(SOURCE, tree);
+        let st_manager = SymbolTableManager::new(uri, ast_manager.get_ast(), &Imports::default());
+        let offset = SOURCE.find(text).unwrap() + text.len();
+        let position = LineIndex::new(SOURCE).position(SOURCE, offset);
+
+        get_signature_help(
+            &Arc::new(Mutex::new(ast_manager)),
+            &Arc::new(Mutex::new(st_manager)),
+            position,
+        )
+        .unwrap_or_else(|| panic!("No signature help after `{}`", text))
+    }
+
+    /// Labels of the signatures, and the active signature and parameter.
+    fn describe(help: SignatureHelp) -> (Vec<String>, Option<u32>, Option<u32>) {
+        (
+            help.signatures
+                .into_iter()
+                .map(|signature| signature.label)
+                .collect(),
+            help.active_signature,
+            help.active_parameter,
+        )
+    }
+
+    #[test]
+    fn test_split_arguments() {
+        assert_eq!(split_arguments("("), Some(vec!["".to_string()]));
+        assert_eq!(
+            split_arguments("<bit<8>>(a, f(b, c), "),
+            Some(vec!["a".to_string(), "f(b, c)".to_string(), "".to_string()])
+        );
+        assert_eq!(split_arguments("(a)"), None);
+        assert_eq!(split_arguments(" = b"), None);
+    }
+
+    #[test]
+    fn test_argument_name() {
+        assert_eq!(argument_end(" = x, index = 0);"), " = x");
+        assert_eq!(argument_end("f(a, b)) + 1"), "f(a, b)");
+
+        let name = |before: &str, after: &str| {
+            get_argument_name(&format!("{}{}", before, argument_end(after)))
+        };
+        assert_eq!(
+            name("value", " = x, index = 0);"),
+            Some("value".to_string())
+        );
+        assert_eq!(name("val", "ue = x)"), Some("value".to_string()));
+        assert_eq!(name("index = ", "0)"), Some("index".to_string()));
+        assert_eq!(name("x", " == y)"), None);
+        assert_eq!(name("x", ", value = y)"), None);
+        assert_eq!(name("f(a", ", b = c))"), None);
+    }
+
+    #[test]
+    fn test_signature_help() {
+        let read = vec!["read(out bit<32> result, in bit<32> index)".to_string()];
+        assert_eq!(
+            describe(signature_help("r.read(")),
+            (read.clone(), Some(0), Some(0))
+        );
+        assert_eq!(
+            describe(signature_help("r.read(x,")),
+            (read, Some(0), Some(1))
+        );
+
+        let write = vec!["write(in bit<32> index, in bit<32> value)".to_string()];
+        assert_eq!(
+            describe(signature_help("r.write(value")),
+            (write, Some(0), Some(1))
+        );
+
+        let extract = vec![
+            "extract(out H hdr)".to_string(),
+            "extract(out H variableSizeHeader, in bit<32> variableFieldSizeInBits)".to_string(),
+        ];
+        assert_eq!(
+            describe(signature_help("pkt.extract(x,")),
+            (extract, Some(1), Some(1))
+        );
+
+        assert_eq!(
+            describe(signature_help("set(x, ")),
+            (
+                vec!["set(in bit<32> v, bit<8> port)".to_string()],
+                Some(0),
+                Some(1)
+            )
+        );
+        assert_eq!(
+            describe(signature_help("sub.apply(")),
+            (vec!["Sub(inout bit<32> x)".to_string()], Some(0), Some(0))
+        );
+        assert_eq!(
+            describe(signature_help("register<bit<32>>(")),
+            (vec!["register(bit<32> size)".to_string()], Some(0), Some(0))
+        );
+    }
+}
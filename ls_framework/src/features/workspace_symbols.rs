@@ -0,0 +1,115 @@
+use tower_lsp::lsp_types::{DocumentSymbol, Location, SymbolInformation, Url};
+
+/// Flattens a document outline, giving each symbol the dotted path of its
+/// parents as container name (e.g. `MyIngress.ipv4_lpm`).
+pub fn flatten(
+    uri: &Url,
+    document_symbols: Vec<DocumentSymbol>,
+    container: Option<String>,
+) -> Vec<SymbolInformation> {
+    let mut symbols = vec![];
+
+    for document_symbol in document_symbols {
+        let path = match &container {
+            Some(container) => format!("{}.{}", container, document_symbol.name),
+            None => document_symbol.name.clone(),
+        };
+
+        #[allow(deprecated)]
+        symbols.push(SymbolInformation {
+            name: document_symbol.name,
+            kind: document_symbol.kind,
+            tags: None,
+            deprecated: None,
+            location: Location::new(uri.clone(), document_symbol.selection_range),
+            container_name: container.clone(),
+        });
+
+        if let Some(children) = document_symbol.children {
+            symbols.append(&mut flatten(uri, children, Some(path)));
+        }
+    }
+
+    symbols
+}
+
+/// Keeps the symbols matching `query` and sorts them from best to worst match.
+pub fn search<'a>(
+    query: &str,
+    symbols: impl IntoIterator<Item = &'a SymbolInformation>,
+) -> Vec<SymbolInformation> {
+    let mut matches: Vec<(i32, &SymbolInformation)> = symbols
+        .into_iter()
+        .filter_map(|symbol| {
+            let score = fuzzy_score(query, &symbol.name).or_else(|| {
+                let qualified_name = match &symbol.container_name {
+                    Some(container) => format!("{}.{}", container, symbol.name),
+                    None => return None,
+                };
+                Some(fuzzy_score(query, &qualified_name)? / 2)
+            })?;
+
+            Some((score, symbol))
+        })
+        .collect();
+    matches.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(a.name.cmp(&b.name)));
+
+    matches
+        .into_iter()
+        .map(|(_, symbol)| symbol.clone())
+        .collect()
+}
+
+/// Scores how well `query` matches `candidate` when its characters appear in
+/// order, case-insensitively. Consecutive characters and characters starting a
+/// word are favored. Returns `None` when `query` isn't a subsequence of `candidate`.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut index = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for query_char in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (index..candidate.len())
+            .find(|i| candidate[*i].to_lowercase().eq(query_char.to_lowercase()))?;
+
+        score += 1;
+        if previous_match.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 {
+            score += 10;
+        } else if is_word_start(&candidate, found) {
+            score += 8;
+        }
+        score -= (found - index) as i32;
+
+        previous_match = Some(found);
+        index = found + 1;
+    }
+
+    Some(score)
+}
+
+fn is_word_start(candidate: &[char], i: usize) -> bool {
+    let (previous, current) = (candidate[i - 1], candidate[i]);
+
+    !previous.is_alphanumeric() || (previous.is_lowercase() && current.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fuzzy_score;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("lpm", "ipv4_lpm").is_some());
+        assert!(fuzzy_score("ipv4lpm", "ipv4_lpm").is_some());
+        assert!(fuzzy_score("mpl", "ipv4_lpm").is_none());
+        assert_eq!(fuzzy_score("", "ipv4_lpm"), Some(0));
+
+        assert!(fuzzy_score("ing", "MyIngress") > fuzzy_score("ing", "forwarding"));
+        assert!(fuzzy_score("ipv4", "ipv4_lpm") > fuzzy_score("ipv4", "set_ipv4_dst"));
+        assert!(fuzzy_score("IPV4", "ipv4_lpm") == fuzzy_score("ipv4", "ipv4_lpm"));
+    }
+}
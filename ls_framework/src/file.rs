@@ -1,14 +1,24 @@
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use tower_lsp::lsp_types::{
-    CompletionContext, CompletionItem, Diagnostic, HoverContents, Location, Position,
-    SemanticTokensResult, TextDocumentContentChangeEvent, Url, WorkspaceEdit,
+    CompletionContext, CompletionItem, Diagnostic, DocumentSymbol, HoverContents, Location,
+    Position, Range, SemanticToken, SemanticTokens, SemanticTokensDelta,
+    SemanticTokensFullDeltaResult, SignatureHelp, SymbolInformation,
+    TextDocumentContentChangeEvent, Url,
 };
 use tree_sitter::{InputEdit, Parser, Tree};
 
-use crate::features::{completion, diagnostics, goto, hover, rename, semantic_tokens};
-use crate::metadata::{AstEditor, AstManager, SymbolTableEditor, SymbolTableManager};
-use crate::utils;
+use crate::features::{
+    completion, diagnostics, document_symbols, goto, hover, rename, semantic_tokens,
+    signature_help, workspace_symbols,
+};
+use crate::include::Include;
+use crate::line_index::LineIndex;
+use crate::metadata::{
+    AstEditor, AstManager, Imports, SourceEdit, SymbolTableEditor, SymbolTableManager,
+};
+use crate::settings::Settings;
 
 pub struct File {
     pub uri: Url,
@@ -16,10 +26,21 @@ pub struct File {
     pub tree: Option<Tree>,
     pub symbol_table_manager: Arc<Mutex<SymbolTableManager>>,
     pub ast_manager: Arc<Mutex<AstManager>>,
+    pub includes: Vec<Include>,
+    /// Last tokens sent to the client, to answer delta requests.
+    semantic_tokens: Mutex<Option<SemanticTokens>>,
+    /// Symbols of the outline, with their container names, indexed for workspace searches.
+    workspace_symbols: Vec<SymbolInformation>,
 }
 
 impl File {
-    pub fn new(uri: Url, source_code: &str, tree: &Option<Tree>) -> File {
+    pub fn new(
+        uri: Url,
+        source_code: &str,
+        tree: &Option<Tree>,
+        includes: Vec<Include>,
+        imports: &Imports,
+    ) -> File {
         let ast_manager = Arc::new(Mutex::new(AstManager::new(
             source_code,
             tree.to_owned().unwrap(),
@@ -27,72 +48,162 @@ impl File {
 
         let symbol_table_manager = {
             let mut ast_manager = ast_manager.lock().unwrap();
-            Arc::new(Mutex::new(SymbolTableManager::new(ast_manager.get_ast())))
+            Arc::new(Mutex::new(SymbolTableManager::new(
+                uri.clone(),
+                ast_manager.get_ast(),
+                imports,
+            )))
         };
 
         debug!("\nAST:\n{}", ast_manager.lock().unwrap());
         debug!("\nSymbol Table:\n{}", symbol_table_manager.lock().unwrap());
 
-        File {
+        let mut file = File {
             uri,
             source_code: source_code.to_string(),
             tree: tree.clone(),
             symbol_table_manager,
             ast_manager,
-        }
+            includes,
+            semantic_tokens: Mutex::new(None),
+            workspace_symbols: vec![],
+        };
+        file.index_workspace_symbols();
+
+        file
     }
 
     pub fn update(&mut self, changes: Vec<TextDocumentContentChangeEvent>, parser: &mut Parser) {
+        let mut ast_manager = self.ast_manager.lock().unwrap();
+
         for change in changes {
-            let mut old_tree: Option<&Tree> = None;
-            let text: String;
-
-            if let Some(range) = change.range {
-                let start_byte = utils::pos_to_byte(range.start, &self.source_code);
-                let old_end_byte = utils::pos_to_byte(range.end, &self.source_code);
-
-                let start_position = utils::pos_to_point(range.start);
-
-                let edit = InputEdit {
-                    start_byte,
-                    old_end_byte: utils::pos_to_byte(range.end, &self.source_code),
-                    new_end_byte: start_byte + change.text.len(),
-                    start_position,
-                    old_end_position: utils::pos_to_point(range.end),
-                    new_end_position: utils::calculate_end_point(start_position, &change.text),
-                };
-
-                self.source_code
-                    .replace_range(start_byte..old_end_byte, &change.text);
-
-                text = self.source_code.clone();
-                let tree = self.tree.as_mut().unwrap();
-                tree.edit(&edit);
-                old_tree = Some(tree);
-            } else {
-                // If change.range is None, change.text represents the whole file
-                text = change.text.clone();
+            match (change.range, self.tree.as_mut()) {
+                (Some(range), Some(tree)) => {
+                    let old_index = LineIndex::new(&self.source_code);
+                    let start_byte = old_index.offset(&self.source_code, range.start);
+                    let old_end_byte = old_index.offset(&self.source_code, range.end);
+                    let new_end_byte = start_byte + change.text.len();
+                    let old_range = Range::new(
+                        old_index.position(&self.source_code, start_byte),
+                        old_index.position(&self.source_code, old_end_byte),
+                    );
+
+                    self.source_code
+                        .replace_range(start_byte..old_end_byte, &change.text);
+                    let new_index = LineIndex::new(&self.source_code);
+
+                    tree.edit(&InputEdit {
+                        start_byte,
+                        old_end_byte,
+                        new_end_byte,
+                        start_position: old_index.point(start_byte),
+                        old_end_position: old_index.point(old_end_byte),
+                        new_end_position: new_index.point(new_end_byte),
+                    });
+
+                    let Some(new_tree) = parser.parse(&self.source_code, Some(tree)) else {
+                        continue;
+                    };
+                    let changed_ranges = tree
+                        .changed_ranges(&new_tree)
+                        .map(|range| new_index.range(&self.source_code, range))
+                        .collect();
+
+                    ast_manager.apply_edit(
+                        &self.source_code,
+                        new_tree.clone(),
+                        SourceEdit {
+                            old_range,
+                            new_end: new_index.position(&self.source_code, new_end_byte),
+                        },
+                        changed_ranges,
+                    );
+                    self.tree = Some(new_tree);
+                }
+                _ => {
+                    // If change.range is None, change.text represents the whole file
+                    self.source_code = change.text;
+                    self.tree = parser.parse(&self.source_code, None);
+
+                    if let Some(tree) = &self.tree {
+                        ast_manager.update(&self.source_code, tree.clone());
+                    }
+                }
             }
+        }
+
+        debug!("\nAST:\n{}", ast_manager);
+    }
 
-            self.tree = parser.parse(text, old_tree);
+    /// Copy of the file to analyze it without holding the workspace. The AST
+    /// and the symbol table are shared until either side changes them.
+    pub fn snapshot(&self) -> File {
+        File {
+            uri: self.uri.clone(),
+            source_code: self.source_code.clone(),
+            tree: self.tree.clone(),
+            symbol_table_manager: Arc::new(Mutex::new(
+                self.symbol_table_manager.lock().unwrap().clone(),
+            )),
+            ast_manager: Arc::new(Mutex::new(self.ast_manager.lock().unwrap().clone())),
+            includes: self.includes.clone(),
+            semantic_tokens: Mutex::new(None),
+            workspace_symbols: vec![],
         }
+    }
 
-        let mut ast_manager = self.ast_manager.lock().unwrap();
-        let mut st_manager = self.symbol_table_manager.lock().unwrap();
+    /// Whether a failure left the analysis data unusable.
+    pub fn is_poisoned(&self) -> bool {
+        self.ast_manager.is_poisoned()
+            || self.symbol_table_manager.is_poisoned()
+            || self.semantic_tokens.is_poisoned()
+    }
 
-        ast_manager.update(&self.source_code, self.tree.to_owned().unwrap());
-        st_manager.update(ast_manager.get_ast());
+    pub fn update_symbol_table(&mut self, includes: Vec<Include>, imports: &Imports) {
+        self.includes = includes;
 
-        debug!("\nAST:\n{}", ast_manager);
-        debug!("\nSymbol Table:\n{}", st_manager);
+        {
+            let mut ast_manager = self.ast_manager.lock().unwrap();
+            let mut st_manager = self.symbol_table_manager.lock().unwrap();
+
+            st_manager.update(self.uri.clone(), ast_manager.get_ast(), imports);
+
+            debug!("\nSymbol Table:\n{}", st_manager);
+        }
+
+        self.index_workspace_symbols();
+    }
+
+    fn index_workspace_symbols(&mut self) {
+        let document_symbols = self.get_document_symbols().unwrap_or_default();
+        self.workspace_symbols = workspace_symbols::flatten(&self.uri, document_symbols, None);
     }
 
-    pub fn get_quick_diagnostics(&self) -> Vec<Diagnostic> {
-        diagnostics::get_quick_diagnostics(&self.ast_manager, &self.symbol_table_manager)
+    pub fn get_workspace_symbols(&self) -> &[SymbolInformation] {
+        &self.workspace_symbols
     }
 
-    pub fn get_full_diagnostics(&self) -> Vec<Diagnostic> {
-        diagnostics::get_full_diagnostics(&self.ast_manager, &self.symbol_table_manager)
+    /// Fingerprint of the symbols the files including this one can see.
+    pub fn get_exports_hash(&self) -> u64 {
+        self.symbol_table_manager
+            .lock()
+            .unwrap()
+            .get_symbol_table()
+            .get_exports_hash()
+    }
+
+    pub fn get_full_diagnostics(&self, settings: &Settings) -> Vec<Diagnostic> {
+        let mut diagnostics = diagnostics::get_full_diagnostics(
+            &self.ast_manager,
+            &self.symbol_table_manager,
+            settings,
+        );
+        diagnostics.append(&mut diagnostics::get_include_diagnostics(&self.includes));
+        diagnostics
+    }
+
+    pub fn get_document_symbols(&self) -> Option<Vec<DocumentSymbol>> {
+        document_symbols::get_document_symbols(&self.symbol_table_manager)
     }
 
     pub fn get_completion_list(
@@ -112,30 +223,332 @@ impl File {
         hover::get_hover_info(&self.ast_manager, &self.symbol_table_manager, position)
     }
 
-    pub fn get_semantic_tokens(&self) -> Option<SemanticTokensResult> {
+    pub fn get_signature_help(&self, position: Position) -> Option<SignatureHelp> {
+        signature_help::get_signature_help(&self.ast_manager, &self.symbol_table_manager, position)
+    }
+
+    pub fn get_semantic_tokens(&self, library_files: &[PathBuf]) -> Option<SemanticTokens> {
+        let tokens = SemanticTokens {
+            result_id: Some(semantic_tokens::new_result_id()),
+            data: self.compute_semantic_tokens(None, library_files)?,
+        };
+        *self.semantic_tokens.lock().unwrap() = Some(tokens.clone());
+
+        Some(tokens)
+    }
+
+    /// Returns the changes since the tokens identified by `previous_result_id`,
+    /// or all the tokens if they aren't the last ones sent.
+    pub fn get_semantic_tokens_delta(
+        &self,
+        previous_result_id: &str,
+        library_files: &[PathBuf],
+    ) -> Option<SemanticTokensFullDeltaResult> {
+        let previous = self.semantic_tokens.lock().unwrap().take();
+        let tokens = self.get_semantic_tokens(library_files)?;
+
+        match previous {
+            Some(previous) if previous.result_id.as_deref() == Some(previous_result_id) => Some(
+                SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                    edits: semantic_tokens::get_edits(&previous.data, &tokens.data),
+                    result_id: tokens.result_id,
+                }),
+            ),
+            _ => Some(SemanticTokensFullDeltaResult::Tokens(tokens)),
+        }
+    }
+
+    pub fn get_semantic_tokens_range(
+        &self,
+        range: Range,
+        library_files: &[PathBuf],
+    ) -> Option<SemanticTokens> {
+        Some(SemanticTokens {
+            result_id: None,
+            data: self.compute_semantic_tokens(Some(range), library_files)?,
+        })
+    }
+
+    fn compute_semantic_tokens(
+        &self,
+        range: Option<Range>,
+        library_files: &[PathBuf],
+    ) -> Option<Vec<SemanticToken>> {
         self.tree.as_ref().map(|ts_tree| {
             semantic_tokens::get_tokens(
                 &self.ast_manager,
                 &self.symbol_table_manager,
                 ts_tree,
                 &self.source_code,
+                range,
+                library_files,
             )
         })
     }
 
     pub fn get_definition_location(&self, position: Position) -> Option<Location> {
-        let range =
-            goto::get_definition_range(&self.ast_manager, &self.symbol_table_manager, position)?;
-        Some(Location::new(self.uri.clone(), range))
+        goto::get_definition_location(&self.ast_manager, &self.symbol_table_manager, position)
     }
 
-    pub fn rename_symbol(&self, position: Position, new_name: String) -> Option<WorkspaceEdit> {
-        rename::rename(
-            &self.ast_manager,
-            &self.symbol_table_manager,
-            self.uri.clone(),
-            new_name,
-            position,
-        )
+    pub fn get_symbol_usages(&self, definition: &Location) -> Vec<Range> {
+        rename::get_usages(&self.symbol_table_manager, definition)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Once;
+
+    use tower_lsp::lsp_types::{Range, TextDocumentContentChangeEvent, Url};
+    use tree_sitter::Parser;
+
+    use super::File;
+    use crate::language_def::LanguageDefinition;
+    use crate::line_index::LineIndex;
+    use crate::metadata::{
+        AstManager, AstQuery, Imports, Symbol, SymbolTableManager, SymbolTableQuery, VisitNode,
+        Visitable,
+    };
+
+    static LOAD_LANGUAGE_DEF: Once = Once::new();
+
+    const SOURCE: &str = "header h_t {
+    bit<8> f;
+}
+
+control C(inout h_t h) {
+    action a() {
+        h.f = 1;
+    }
+    apply {
+        a();
+    }
+}
+";
+
+    /// Returns a P4 parser, after loading the P4 rules shared by all tests.
+    pub(crate) fn new_parser() -> Parser {
+        LOAD_LANGUAGE_DEF.call_once(|| {
+            LanguageDefinition::load(include_str!("../../p4_lsf/language_def/rules.ron"))
+        });
+
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_p4::language()).unwrap();
+        parser
+    }
+
+    fn replace(file: &mut File, parser: &mut Parser, old_text: &str, new_text: &str) {
+        let start = file.source_code.find(old_text).unwrap();
+        edit(file, parser, start..start + old_text.len(), new_text);
+    }
+
+    fn edit(file: &mut File, parser: &mut Parser, bytes: std::ops::Range<usize>, new_text: &str) {
+        let line_index = LineIndex::new(&file.source_code);
+        let range = Range::new(
+            line_index.position(&file.source_code, bytes.start),
+            line_index.position(&file.source_code, bytes.end),
+        );
+
+        file.update(
+            vec![TextDocumentContentChangeEvent {
+                range: Some(range),
+                range_length: None,
+                text: new_text.to_string(),
+            }],
+            parser,
+        );
+        file.update_symbol_table(vec![], &Imports::default());
+    }
+
+    /// Prints a subtree with the children sorted, so the order in which nodes
+    /// were translated doesn't matter.
+    fn dump(node: VisitNode) -> String {
+        let data = node.get();
+        let mut children: Vec<String> = node.get_children().into_iter().map(dump).collect();
+        children.sort();
+
+        let mut output = format!(
+            "{:?} {:?} {:?} {:?} {:?}\n",
+            data.kind, data.range, data.content, data.symbol, data.semantic_token_type
+        );
+        for line in children.concat().lines() {
+            output.push_str(&format!("  {}\n", line));
+        }
+
+        output
+    }
+
+    /// Prints the symbols of a table with what their usages, types and fields
+    /// resolve to, and the symbols the nodes of the AST are linked to, sorted so
+    /// the layout of the arenas doesn't matter.
+    fn dump_symbols(ast_manager: &AstManager, st_manager: &SymbolTableManager) -> String {
+        let describe =
+            |symbol: &Symbol| format!("{} {:?}", symbol.get_name(), symbol.get_definition_range());
+
+        let mut lines: Vec<String> = st_manager
+            .get_all_symbols()
+            .iter()
+            .map(|symbol| {
+                let mut usages = symbol.get_usages().clone();
+                usages.sort_by_key(|range| (range.start, range.end));
+                let type_symbol = symbol
+                    .get_type_symbol()
+                    .and_then(|id| st_manager.get_symbol(id))
+                    .map(describe);
+                let fields = symbol.get_field_scope_id().map(|id| {
+                    let mut fields: Vec<String> = st_manager
+                        .get_symbols_in_scope(id)
+                        .iter()
+                        .map(describe)
+                        .collect();
+                    fields.sort();
+                    fields
+                });
+
+                format!(
+                    "{} {} {:?} {:?} {:?} {:?}",
+                    describe(symbol),
+                    symbol.get_kind(),
+                    usages,
+                    type_symbol,
+                    fields,
+                    symbol.get_declaration()
+                )
+            })
+            .collect();
+        lines.extend(
+            ast_manager
+                .visit_root()
+                .get_descendants()
+                .into_iter()
+                .filter_map(|node| {
+                    let symbol = st_manager.get_symbol(node.get().linked_symbol.clone()?);
+                    Some(format!(
+                        "{:?} -> {:?}",
+                        node.get().range,
+                        symbol.map(describe)
+                    ))
+                }),
+        );
+        lines.extend(
+            st_manager
+                .get_undefined_symbols()
+                .iter()
+                .map(|symbol| format!("{:?}", symbol)),
+        );
+        lines.sort();
+
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_incremental_ast_matches_full_build() {
+        let mut parser = new_parser();
+        let tree = parser.parse(SOURCE, None);
+        let uri = Url::parse("file:///test.p4").unwrap();
+        let mut file = File::new(uri, SOURCE, &tree, vec![], &Imports::default());
+
+        let edits = [
+            ("bit<8> f;", "bit<8> field;"),
+            ("\ncontrol", "\nconst bit<8> X = 2;\n\ncontrol"),
+            ("h.f = 1;", "h.f = X;\n        h.f = 3;"),
+            ("const bit<8> X = 2;\n\n", ""),
+            ("action a() {", "action a( {"),
+            ("action a( {", "action a() {"),
+            ("}\n\ncontrol", "}\ncontrol"),
+        ];
+
+        for (old_text, new_text) in edits {
+            replace(&mut file, &mut parser, old_text, new_text);
+
+            assert_matches_full_build(&file, &mut parser, &format!("{old_text:?} -> {new_text:?}"));
+        }
+    }
+
+    #[test]
+    fn test_random_edits_match_full_build() {
+        let snippets = [
+            "",
+            "x",
+            "é",
+            "😀",
+            "\r\n",
+            "\n",
+            ";",
+            "{",
+            "}",
+            "(",
+            ")",
+            " ",
+            "bit<8> ",
+            "// Entête modifiée\r\n",
+            "/* ça */",
+            "h.f = 2;\r\n",
+            "action b() {}\n",
+        ];
+        let mut parser = new_parser();
+        let source = SOURCE.replace("    ", "\t").replace('\n', "\r\n");
+        let tree = parser.parse(&source, None);
+        let uri = Url::parse("file:///test.p4").unwrap();
+        let mut file = File::new(uri, &source, &tree, vec![], &Imports::default());
+
+        let mut seed = 0x9e37_79b9_u64;
+        let mut random = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+
+        for _ in 0..300 {
+            let boundaries: Vec<usize> = file
+                .source_code
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain([file.source_code.len()])
+                .filter(|&i| !file.source_code[..i].ends_with('\r'))
+                .collect();
+            let start = boundaries[random(boundaries.len())];
+            let end = boundaries[random(boundaries.len())]
+                .max(start)
+                .min(start + 12);
+            let end = *boundaries.iter().find(|&&i| i >= end).unwrap();
+            let new_text = snippets[random(snippets.len())];
+
+            edit(&mut file, &mut parser, start..end, new_text);
+            assert_matches_full_build(
+                &file,
+                &mut parser,
+                &format!("{start}..{end} -> {new_text:?}"),
+            );
+        }
+    }
+
+    fn assert_matches_full_build(file: &File, parser: &mut Parser, description: &str) {
+        let full_tree = parser.parse(&file.source_code, None).unwrap();
+        assert_eq!(
+            file.tree.as_ref().unwrap().root_node().to_sexp(),
+            full_tree.root_node().to_sexp(),
+            "syntax tree after {}",
+            description
+        );
+
+        let mut full = AstManager::new(&file.source_code, full_tree);
+        let ast_manager = file.ast_manager.lock().unwrap();
+        assert_eq!(
+            dump(ast_manager.visit_root()),
+            dump(full.visit_root()),
+            "AST after {}",
+            description
+        );
+
+        let full_st =
+            SymbolTableManager::new(file.uri.clone(), full.get_ast(), &Imports::default());
+        assert_eq!(
+            dump_symbols(&ast_manager, &file.symbol_table_manager.lock().unwrap()),
+            dump_symbols(&full, &full_st),
+            "symbol table after {}",
+            description
+        );
     }
 }
@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::RwLock;
+use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
+use tower_lsp::lsp_types::{MessageType, Url};
+use tower_lsp::Client;
+
+use crate::utils;
+use crate::workspace::Workspace;
+
+/// Gives access to the workspace while isolating failures: a panic during an
+/// analysis only fails the current request instead of the whole server.
+#[derive(Clone)]
+pub struct Guard {
+    client: Client,
+    workspace: Arc<RwLock<Workspace>>,
+    reported_failures: Arc<Mutex<HashSet<&'static str>>>,
+}
+
+impl Guard {
+    pub fn new(client: Client, workspace: Workspace) -> Guard {
+        Guard {
+            client,
+            workspace: Arc::new(workspace.into()),
+            reported_failures: Arc::new(HashSet::new().into()),
+        }
+    }
+
+    /// Runs `f` with shared access to the workspace. `failure_class` names the
+    /// failing operation and `url` the file it was working on, if any.
+    pub async fn read<T>(
+        &self,
+        failure_class: &'static str,
+        url: Option<&Url>,
+        f: impl FnOnce(&Workspace) -> T,
+    ) -> Result<T> {
+        let result = {
+            let workspace = self.workspace.read().await;
+            utils::catch_panic(|| f(&workspace))
+        };
+
+        self.check(failure_class, url, result).await
+    }
+
+    /// Runs `f` with exclusive access to the workspace, see [`Guard::read`].
+    pub async fn write<T>(
+        &self,
+        failure_class: &'static str,
+        url: Option<&Url>,
+        f: impl FnOnce(&mut Workspace) -> T,
+    ) -> Result<T> {
+        let result = {
+            let mut workspace = self.workspace.write().await;
+            utils::catch_panic(|| f(&mut workspace))
+        };
+
+        self.check(failure_class, url, result).await
+    }
+
+    /// Runs `f` on a thread reserved for blocking work, for analyses of data
+    /// taken out of the workspace that would otherwise hold up the other
+    /// requests. Failures are handled as in [`Guard::read`].
+    pub async fn run_blocking<T: Send + 'static>(
+        &self,
+        failure_class: &'static str,
+        url: Option<&Url>,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T> {
+        let result = tokio::task::spawn_blocking(|| utils::catch_panic(f))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+
+        self.check(failure_class, url, result).await
+    }
+
+    /// Turns a failure into an error response, after rebuilding the files it
+    /// may have left in an inconsistent state. The user is warned once per class.
+    async fn check<T>(
+        &self,
+        failure_class: &'static str,
+        url: Option<&Url>,
+        result: std::result::Result<T, String>,
+    ) -> Result<T> {
+        let message = match result {
+            Ok(value) => return Ok(value),
+            Err(message) => message,
+        };
+
+        error!("{} failed: {}", failure_class, message);
+        self.workspace.write().await.recover(url);
+
+        let first_failure = self.reported_failures.lock().unwrap().insert(failure_class);
+        if first_failure {
+            self.client
+                .show_message(
+                    MessageType::WARNING,
+                    format!(
+                        "Internal error during {}, the server recovered but results may be incomplete. See the log for details.",
+                        failure_class
+                    ),
+                )
+                .await;
+        }
+
+        Err(Error {
+            code: ErrorCode::InternalError,
+            message: format!("{} failed: {}", failure_class, message).into(),
+            data: None,
+        })
+    }
+}
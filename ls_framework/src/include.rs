@@ -0,0 +1,199 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use tower_lsp::lsp_types::{Range, Url};
+
+use crate::line_index::LineIndex;
+
+lazy_static! {
+    static ref INCLUDE_REGEX: Regex =
+        Regex::new(r#"(?m)^[ \t]*#[ \t]*include[ \t]*(?:<([^>\r\n]+)>|"([^"\r\n]+)")"#).unwrap();
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum IncludeKind {
+    /// `#include <file>`
+    System,
+    /// `#include "file"`
+    Local,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Include {
+    pub path: String,
+    pub kind: IncludeKind,
+    pub range: Range,
+    pub resolved: Option<Url>,
+}
+
+pub fn find_includes(source_code: &str) -> Vec<Include> {
+    let line_index = LineIndex::new(source_code);
+
+    INCLUDE_REGEX
+        .captures_iter(source_code)
+        .filter_map(|captures| {
+            let directive = captures.get(0)?;
+            let (path, kind) = if let Some(path) = captures.get(1) {
+                (path.as_str(), IncludeKind::System)
+            } else {
+                (captures.get(2)?.as_str(), IncludeKind::Local)
+            };
+
+            Some(Include {
+                path: path.trim().to_string(),
+                kind,
+                range: Range::new(
+                    line_index.position(source_code, directive.start()),
+                    line_index.position(source_code, directive.end()),
+                ),
+                resolved: None,
+            })
+        })
+        .collect()
+}
+
+/// Looks for the included file in the directory of the including file and in the
+/// search paths. Quoted includes check the including file's directory first,
+/// bracketed includes check it last. `fallback_dir` is only checked once every
+/// directory on disk missed, so the user's files take precedence over it.
+pub fn resolve(
+    include: &Include,
+    including_file: &Url,
+    search_paths: &[PathBuf],
+    fallback_dir: Option<&Path>,
+) -> Option<Url> {
+    let local_dir = including_file
+        .to_file_path()
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf));
+
+    let mut candidates: Vec<&Path> = search_paths.iter().map(PathBuf::as_path).collect();
+    if let Some(local_dir) = &local_dir {
+        match include.kind {
+            IncludeKind::Local => candidates.insert(0, local_dir),
+            IncludeKind::System => candidates.push(local_dir),
+        }
+    }
+    candidates.extend(fallback_dir);
+
+    candidates
+        .into_iter()
+        .map(|dir| dir.join(&include.path))
+        .find(|path| path.is_file())
+        .and_then(|path| Url::from_file_path(path.canonicalize().unwrap_or(path)).ok())
+}
+
+/// Writes the library files shipped with the server to a new directory of
+/// `parent` only the current user can access, so they can be resolved like any
+/// other include and opened by the client. The directory is named after `prefix`
+/// and the process, an existing path is never reused.
+pub fn install_bundled_files(
+    parent: &Path,
+    prefix: &str,
+    files: &[(&str, &str)],
+) -> io::Result<PathBuf> {
+    let mut attempt = 0;
+    let dir = loop {
+        let dir = parent.join(format!("{}-{}-{}", prefix, process::id(), attempt));
+        match create_private_dir(&dir) {
+            Ok(()) => break dir,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    };
+
+    for (name, content) in files {
+        fs::write(dir.join(name), content)?;
+    }
+
+    Ok(dir)
+}
+
+/// Creates a directory, failing if anything (including a symbolic link) is
+/// already at its path.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(dir)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+    use std::{env, fs};
+
+    use tower_lsp::lsp_types::{Position, Range, Url};
+
+    use super::{find_includes, install_bundled_files, resolve, IncludeKind};
+
+    /// Directory of files created for a test, removed even when the test fails.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(prefix: &str, files: &[(&str, &str)]) -> TempDir {
+            let dir = install_bundled_files(&env::temp_dir(), prefix, files).unwrap();
+            // Resolved includes are canonical, the temporary directory may be a symbolic link
+            TempDir(dir.canonicalize().unwrap())
+        }
+
+        pub(crate) fn path(&self) -> &Path {
+            &self.0
+        }
+
+        pub(crate) fn url(&self, name: &str) -> Url {
+            Url::from_file_path(self.0.join(name)).unwrap()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_find_includes() {
+        let source =
+            "/* header */\n#include <core.p4>\n  # include \"headers.p4\"\nconst bit<8> X = 1;";
+        let includes = find_includes(source);
+
+        assert_eq!(includes.len(), 2);
+        assert_eq!(includes[0].path, "core.p4");
+        assert_eq!(includes[0].kind, IncludeKind::System);
+        assert_eq!(
+            includes[0].range,
+            Range::new(Position::new(1, 0), Position::new(1, 18))
+        );
+        assert_eq!(includes[1].path, "headers.p4");
+        assert_eq!(includes[1].kind, IncludeKind::Local);
+        assert_eq!(includes[1].range.start, Position::new(2, 0));
+    }
+
+    #[test]
+    fn test_resolve_prefers_disk() {
+        let bundled = TempDir::new("test-bundled", &[("core.p4", ""), ("v1model.p4", "")]);
+        let project = TempDir::new("test-project", &[("main.p4", ""), ("core.p4", "")]);
+        let main = project.url("main.p4");
+        let includes = find_includes("#include <core.p4>\n#include <v1model.p4>\n");
+
+        let resolved: Vec<Url> = includes
+            .iter()
+            .map(|include| resolve(include, &main, &[], Some(bundled.path())).unwrap())
+            .collect();
+        assert_eq!(resolved[0], project.url("core.p4"));
+        assert_eq!(resolved[1], bundled.url("v1model.p4"));
+        assert_eq!(
+            resolve(&includes[0], &main, &[], None),
+            Some(resolved[0].clone())
+        );
+    }
+}
@@ -5,8 +5,8 @@ use serde::Deserialize;
 use tokio::sync::OnceCell;
 use tower_lsp::lsp_types::{self, SemanticTokensLegend};
 
-use crate::lsp_mappings::{HighlightType, SymbolCompletionType};
-use crate::metadata::NodeKind;
+use crate::lsp_mappings::{HighlightModifier, HighlightType, OutlineType, SymbolCompletionType};
+use crate::metadata::{Literal, NodeKind, TypeDecType};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Rule {
@@ -17,6 +17,9 @@ pub struct Rule {
     pub is_scope: bool,
     #[serde(default)]
     pub children: Vec<Child>,
+    /// Symbols declared in the scope of every node of the rule, as their name and type.
+    #[serde(default)]
+    pub builtin_symbols: Vec<(String, String)>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -62,6 +65,174 @@ pub struct SymbolDef {
     pub name: String,
     pub completion_type: SymbolCompletionType,
     pub highlight_type: HighlightType,
+    /// Kind shown in the document outline, symbols without one are left out.
+    #[serde(default)]
+    pub outline_type: Option<OutlineType>,
+    /// Modifiers added to the highlighting of the symbol when their condition holds.
+    #[serde(default)]
+    pub highlight_modifiers: Vec<(HighlightModifier, ModifierCondition)>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub enum ModifierCondition {
+    /// Every occurrence of the symbol.
+    Always,
+    /// The occurrence where the symbol is defined.
+    Definition,
+    /// Symbols defined in a library file bundled with the server, or in a file
+    /// of the include paths with the name of one (e.g. `v1model.p4`).
+    Library,
+    /// Symbols whose declaration has a child node of the given name with the
+    /// given text, possibly followed by arguments: `Child("Annotation", "@deprecated")`.
+    Child(String, String),
+}
+
+impl ModifierCondition {
+    /// Whether the text of a declaration's child satisfies a `Child` condition.
+    pub fn matches_child(&self, node_name: &str, content: &str) -> bool {
+        let ModifierCondition::Child(name, text) = self else {
+            return false;
+        };
+
+        name == node_name
+            && content
+                .trim()
+                .strip_prefix(text.as_str())
+                .is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+    }
+}
+
+/// Nodes the type checker gives a meaning to, by the name of their rule.
+/// Expressions, names and members are found through the symbol of their rule.
+#[derive(Debug, Deserialize, Clone)]
+pub struct TypeChecking {
+    /// Rules declaring a type, with the kind of type they declare.
+    pub type_declarations: Vec<(String, TypeDecType)>,
+    pub operator: String,
+    pub literals: Vec<(String, Literal)>,
+    /// Rules assigning their expression to the location written in the given child.
+    pub assignments: Vec<(String, String)>,
+    /// Declarations whose expression initializes the declared symbol.
+    pub initializers: Vec<String>,
+    /// Rules whose expression must be a boolean.
+    pub conditions: Vec<String>,
+}
+
+/// Nodes and symbols detailed when hovering a symbol, by the name of their rule or type.
+#[derive(Debug, Deserialize, Clone)]
+pub struct HoverDetails {
+    /// Symbol types whose parameters are listed, their type being the returned one.
+    pub callables: Vec<String>,
+    /// Symbol types listed as the parameters of the callable declaring them.
+    pub parameters: Vec<String>,
+    /// Child of a declaration giving its direction: `in`, `out` or `inout`.
+    pub direction: String,
+}
+
+/// Rules of the tables, detailed on hover and validated, e.g. the tables of a P4 control.
+#[derive(Debug, Deserialize, Clone)]
+pub struct TableDetails {
+    /// Rule of the body of a table, holding its keys, actions and properties.
+    pub table: String,
+    /// Rule of a key, with the children giving its expression and match kind.
+    pub key: String,
+    pub key_expression: String,
+    pub match_kind: String,
+    /// Rule listing the actions a table can run, and rule of each of them.
+    pub actions: String,
+    pub action: String,
+    /// Symbol types of the actions, and of the parameters they are called with.
+    pub action_types: Vec<String>,
+    pub action_parameters: Vec<String>,
+    /// Rule of a property written `name = value`, with the children giving its name and value.
+    pub property: String,
+    pub property_name: String,
+    pub property_value: String,
+    /// Properties naming the action run on a miss and the number of entries.
+    pub default_action: String,
+    pub size: String,
+    /// Rule of a constant entry, with the child giving its keyset.
+    pub entry: String,
+    pub entry_keyset: String,
+    /// Properties and match kinds every table can use.
+    pub properties: Vec<String>,
+    pub match_kinds: Vec<String>,
+    /// Those that can be used only in a given architecture.
+    #[serde(default)]
+    pub architectures: Vec<Architecture>,
+}
+
+/// Properties and match kinds added by an architecture, used when a file includes it.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Architecture {
+    /// Name of the included file declaring the architecture.
+    pub file: String,
+    pub properties: Vec<String>,
+    pub match_kinds: Vec<String>,
+}
+
+/// Declarations forming a state machine, e.g. the states of a parser. The
+/// machines are the scopes declaring symbols of the state type, their final
+/// states being the built-in symbols of that type.
+#[derive(Debug, Deserialize, Clone)]
+pub struct StateMachine {
+    /// Symbol type of the states.
+    pub state: String,
+    /// Name of the state the machine starts in.
+    pub start: String,
+    /// Rule of the usages naming the next state of a transition.
+    pub next_state: String,
+    pub select: Option<SelectDetails>,
+}
+
+/// Rules of a transition selecting the next state by matching values against keysets.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SelectDetails {
+    pub select: String,
+    /// Child of the select listing the matched values.
+    pub values: String,
+    pub case: String,
+    /// Child of a case giving the keyset the values are matched against.
+    pub keyset: String,
+}
+
+/// Rules of the calls, resolved to the callables they call by the text naming them,
+/// e.g. `f`, `pkt.extract` or `c.apply`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Calls {
+    /// Rules of a call, with the children naming the callable and listing the arguments.
+    pub rules: Vec<(String, String, String)>,
+    /// Rules instantiating a type, with the children giving the type and listing the
+    /// arguments of its constructor, a member named after the type.
+    pub instantiations: Vec<(String, String, String)>,
+    pub argument: String,
+    /// Child of an argument naming the parameter it is passed to.
+    pub argument_name: String,
+    /// Symbol types of the parameters of a callable.
+    pub parameters: Vec<String>,
+    /// Texts in the declaration of a parameter making it optional, e.g. an annotation
+    /// or a default value.
+    pub optional: Vec<String>,
+    /// Method applying an instance, whose parameters are those of the instance's type.
+    pub apply: String,
+}
+
+/// Rules checked for the directions of the parameters, using the calls to know
+/// which arguments are written.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Directions {
+    /// Rules of the callables whose `out` parameters must be assigned before being
+    /// read, with the child holding their body.
+    pub bodies: Vec<(String, String)>,
+    /// Rules assigning the location written in the given child.
+    pub assignments: Vec<(String, String)>,
+    /// Rules running one of the blocks they contain, e.g. an `if` or a `switch`.
+    pub branches: Vec<String>,
+    pub block: String,
+    /// Rules after which the rest of a block isn't run, e.g. a `return`.
+    pub exits: Vec<String>,
+    /// Symbol types of the actions, whose directionless parameters are bound by the control plane.
+    pub actions: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -77,12 +248,29 @@ pub struct LanguageDefinition {
     pub symbol_types: Vec<SymbolDef>,
     pub global_ast_rules: Vec<Child>,
     pub ast_rules: Vec<Rule>,
+    /// Languages without it aren't type checked.
+    #[serde(default)]
+    pub type_checking: Option<TypeChecking>,
+    /// Hovers of languages without it only show the declaration and its type.
+    #[serde(default)]
+    pub hover: Option<HoverDetails>,
+    #[serde(default)]
+    pub state_machine: Option<StateMachine>,
+    /// Languages without it have no tables to detail or validate.
+    #[serde(default)]
+    pub tables: Option<TableDetails>,
+    #[serde(default)]
+    pub calls: Option<Calls>,
+    #[serde(default)]
+    pub directions: Option<Directions>,
 }
 
 static INSTANCE: OnceCell<LanguageDefinition> = OnceCell::const_new();
 static SCOPE_NODES: OnceCell<Vec<NodeKind>> = OnceCell::const_new();
 static KEYWORDS: OnceCell<HashSet<String>> = OnceCell::const_new();
 static SEMANTIC_TOKEN_TYPES: OnceCell<Vec<lsp_types::SemanticTokenType>> = OnceCell::const_new();
+static SEMANTIC_TOKEN_MODIFIERS: OnceCell<Vec<lsp_types::SemanticTokenModifier>> =
+    OnceCell::const_new();
 
 impl LanguageDefinition {
     pub fn load(language_definition: &str) {
@@ -116,6 +304,17 @@ impl LanguageDefinition {
             .set(instance.init_semanc_token_types())
             .unwrap();
 
+        SEMANTIC_TOKEN_MODIFIERS
+            .set(
+                instance
+                    .symbol_types
+                    .iter()
+                    .flat_map(|s| s.highlight_modifiers.iter().map(|(m, _)| m.get()))
+                    .unique()
+                    .collect(),
+            )
+            .unwrap();
+
         KEYWORDS
             .set(HashSet::from_iter(instance.keywords.clone()))
             .unwrap();
@@ -162,13 +361,35 @@ impl LanguageDefinition {
             .expect("LanguageDefinition has not been loaded.")
     }
 
+    pub fn get_semantic_token_modifiers() -> &'static Vec<lsp_types::SemanticTokenModifier> {
+        SEMANTIC_TOKEN_MODIFIERS
+            .get()
+            .expect("LanguageDefinition has not been loaded.")
+    }
+
     pub fn get_semantic_token_legend() -> SemanticTokensLegend {
         SemanticTokensLegend {
             token_types: LanguageDefinition::get_semantic_token_types().clone(),
-            token_modifiers: vec![],
+            token_modifiers: LanguageDefinition::get_semantic_token_modifiers().clone(),
         }
     }
 
+    pub fn get_symbol_def(&self, kind: &str) -> Option<&SymbolDef> {
+        self.symbol_types
+            .iter()
+            .find(|symbol_type| symbol_type.name == kind)
+    }
+
+    /// Kind of type declared by the nodes of the rule `node_name`, if any.
+    pub fn get_type_declaration(&self, node_name: &str) -> Option<TypeDecType> {
+        self.type_checking
+            .as_ref()?
+            .type_declarations
+            .iter()
+            .find(|(name, _)| name == node_name)
+            .map(|(_, declaration)| *declaration)
+    }
+
     pub fn get_scope_nodes() -> &'static Vec<NodeKind> {
         SCOPE_NODES
             .get()
@@ -1,7 +1,15 @@
+use std::collections::HashMap;
 use std::env;
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
+use tokio::task::JoinHandle;
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+
+use crate::guard::Guard;
 use crate::language_def::{self, LanguageDefinition};
+use crate::line_index::PositionEncoding;
 use crate::plugin_manager::{self, OnState, PluginManager, PluginsResult};
 use crate::workspace::Workspace;
 use tower_lsp::jsonrpc::Result;
@@ -14,20 +22,289 @@ use simplelog::*;
 
 use std::fs::File;
 
+/// Time without changes to a document before its diagnostics are computed.
+const DIAGNOSTICS_DELAY: Duration = Duration::from_millis(300);
+
 pub struct Backend {
     client: Client,
-    workspace: RwLock<Workspace>,
+    guard: Guard,
     plugin_manager: RwLock<PluginManager>,
+    client_capabilities: RwLock<ClientCapabilities>,
+    analysis: Analysis,
+}
+
+/// Analyzes the open documents in the background once they stop changing.
+#[derive(Clone)]
+struct Analysis {
+    client: Client,
+    guard: Guard,
+    tasks: Arc<Mutex<HashMap<Url, JoinHandle<()>>>>,
+}
+
+impl Analysis {
+    /// Schedules the analysis of a document, replacing the run scheduled for a
+    /// previous version. Its diagnostics are only published if the document is
+    /// still at the same version.
+    fn schedule(&self, uri: Url, version: i32) {
+        let analysis = self.clone();
+        let task = tokio::spawn({
+            let uri = uri.clone();
+            async move { analysis.run(uri, version).await }
+        });
+
+        if let Some(previous) = self.tasks.lock().unwrap().insert(uri, task) {
+            previous.abort();
+        }
+    }
+
+    fn cancel(&self, uri: &Url) {
+        if let Some(task) = self.tasks.lock().unwrap().remove(uri) {
+            task.abort();
+        }
+    }
+
+    async fn run(&self, uri: Url, version: i32) {
+        tokio::time::sleep(DIAGNOSTICS_DELAY).await;
+
+        let is_current = |workspace: &Workspace| workspace.get_version(&uri) == Some(version);
+        let result = self
+            .update("analysis", Some(&uri), |workspace| {
+                if is_current(workspace) {
+                    workspace.analyze_file(&uri)
+                } else {
+                    vec![]
+                }
+            })
+            .await;
+        if result.is_err() {
+            return;
+        }
+
+        let Ok(Some(diagnostics)) = self.compute_diagnostics(&uri).await else {
+            return;
+        };
+
+        // A newer version may have arrived during the analysis
+        if self.guard.read("diagnostics", None, is_current).await != Ok(true) {
+            return;
+        }
+
+        self.client
+            .publish_diagnostics(uri, diagnostics, Some(version))
+            .await;
+    }
+
+    /// Runs `f` with exclusive access to the workspace, then schedules the
+    /// analysis of the open documents it returns, e.g. the files including a
+    /// changed file.
+    async fn update(
+        &self,
+        failure_class: &'static str,
+        url: Option<&Url>,
+        f: impl FnOnce(&mut Workspace) -> Vec<Url>,
+    ) -> Result<()> {
+        let documents = self
+            .guard
+            .write(failure_class, url, |workspace| {
+                f(workspace)
+                    .into_iter()
+                    .filter_map(|url| workspace.get_version(&url).map(|version| (url, version)))
+                    .collect::<Vec<(Url, i32)>>()
+            })
+            .await?;
+
+        for (uri, version) in documents {
+            self.schedule(uri, version);
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds the symbol table of a document edited since its last analysis,
+    /// for requests that can't wait for the scheduled one.
+    async fn update_symbols(&self, uri: &Url) {
+        let is_stale = self
+            .guard
+            .read("analysis", Some(uri), |workspace| workspace.is_stale(uri))
+            .await;
+
+        if is_stale == Ok(true) {
+            let _ = self
+                .guard
+                .write("analysis", Some(uri), |workspace| {
+                    workspace.update_symbols(uri)
+                })
+                .await;
+        }
+    }
+
+    /// Computes the diagnostics of a file on a snapshot of it, off the async
+    /// workers, so the workspace stays available to the other requests.
+    async fn compute_diagnostics(&self, uri: &Url) -> Result<Option<Vec<Diagnostic>>> {
+        let task = self
+            .guard
+            .read("diagnostics", Some(uri), |workspace| {
+                workspace.get_diagnostics_task(uri)
+            })
+            .await?;
+
+        match task {
+            Some(task) => self
+                .guard
+                .run_blocking("diagnostics", Some(uri), task)
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
+    }
 }
 
 impl Backend {
-    pub fn init(client: Client, ts_language: tree_sitter::Language) -> Backend {
+    pub fn init(
+        client: Client,
+        ts_language: tree_sitter::Language,
+        bundled_includes: &'static [(&'static str, &'static str)],
+    ) -> Backend {
+        let guard = Guard::new(
+            client.clone(),
+            Workspace::new(ts_language, bundled_includes),
+        );
+
         Backend {
+            analysis: Analysis {
+                client: client.clone(),
+                guard: guard.clone(),
+                tasks: Arc::new(HashMap::new().into()),
+            },
+            guard,
             client,
-            workspace: Workspace::new(ts_language).into(),
             plugin_manager: PluginManager::new().into(),
+            client_capabilities: ClientCapabilities::default().into(),
+        }
+    }
+
+    async fn register_file_watchers(&self) {
+        let supported = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        if !supported {
+            return;
+        }
+
+        let extensions = LanguageDefinition::get().language.file_extensions.join(",");
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String(format!("**/*.{{{}}}", extensions)),
+                kind: None,
+            }],
+        };
+        let registration = Registration {
+            id: "watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            warn!("Couldn't register file watchers: {}", e);
+        }
+    }
+
+    /// Indexes the workspace files one by one, so requests can still be served
+    /// in between, and reports the progress to the client.
+    async fn index_workspace(&self) {
+        let urls = self
+            .guard
+            .read("indexing", None, |workspace| {
+                workspace.find_unindexed_files()
+            })
+            .await
+            .unwrap_or_default();
+        if urls.is_empty() {
+            return;
+        }
+
+        let token = NumberOrString::String("indexing".to_string());
+        let supports_progress = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .window
+            .as_ref()
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false);
+        let report_progress = supports_progress
+            && self
+                .client
+                .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                })
+                .await
+                .is_ok();
+
+        let language_name = &LanguageDefinition::get().language.name;
+        let total = urls.len();
+        info!("Indexing {} {} files", total, language_name);
+
+        if report_progress {
+            self.send_progress(
+                &token,
+                WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                    title: "Indexing".to_string(),
+                    cancellable: Some(false),
+                    message: Some(format!("0/{} {} files", total, language_name)),
+                    percentage: Some(0),
+                }),
+            )
+            .await;
+        }
+
+        for (i, url) in urls.into_iter().enumerate() {
+            let _ = self
+                .guard
+                .write("indexing", Some(&url), |workspace| {
+                    workspace.index_file(url.clone())
+                })
+                .await;
+
+            if report_progress {
+                self.send_progress(
+                    &token,
+                    WorkDoneProgress::Report(WorkDoneProgressReport {
+                        cancellable: Some(false),
+                        message: Some(format!("{}/{} {} files", i + 1, total, language_name)),
+                        percentage: Some(((i + 1) * 100 / total) as u32),
+                    }),
+                )
+                .await;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        if report_progress {
+            self.send_progress(
+                &token,
+                WorkDoneProgress::End(WorkDoneProgressEnd {
+                    message: Some(format!("Indexed {} {} files", total, language_name)),
+                }),
+            )
+            .await;
         }
     }
+
+    async fn send_progress(&self, token: &NumberOrString, progress: WorkDoneProgress) {
+        self.client
+            .send_notification::<Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(progress),
+            })
+            .await;
+    }
 }
 
 #[tower_lsp::async_trait]
@@ -54,6 +331,30 @@ impl LanguageServer for Backend {
 
         info!("Initializing lsp");
 
+        *self.client_capabilities.write().unwrap() = params.capabilities.clone();
+
+        let position_encoding = PositionEncoding::negotiate(&params.capabilities);
+        position_encoding.set();
+        info!("Position encoding: {:?}", position_encoding);
+
+        let roots = match (&params.workspace_folders, &params.root_uri) {
+            (Some(folders), _) => folders.iter().map(|folder| &folder.uri).collect(),
+            (None, Some(root_uri)) => vec![root_uri],
+            (None, None) => vec![],
+        };
+
+        let roots = roots
+            .into_iter()
+            .filter_map(|uri| uri.to_file_path().ok())
+            .collect();
+        let _ = self
+            .guard
+            .write("initialization", None, |workspace| {
+                workspace.install_bundled_includes();
+                workspace.set_roots(roots);
+            })
+            .await;
+
         if let Some(options) = params.initialization_options {
             info!("Init options: {}", options);
             self.plugin_manager
@@ -66,10 +367,11 @@ impl LanguageServer for Backend {
 
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
+                position_encoding: Some(position_encoding.kind()),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensOptions(
                         SemanticTokensOptions {
-                            range: Some(false),
+                            range: Some(true),
                             legend: language_def::LanguageDefinition::get_semantic_token_legend(),
                             full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
                             ..Default::default()
@@ -77,6 +379,10 @@ impl LanguageServer for Backend {
                     ),
                 ),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![String::from("("), String::from(",")]),
+                    ..Default::default()
+                }),
                 completion_provider: Some(CompletionOptions {
                     trigger_characters: Some(vec![String::from(".")]),
                     ..Default::default()
@@ -91,6 +397,9 @@ impl LanguageServer for Backend {
                     },
                 )),
                 definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
                 rename_provider: Some(OneOf::Right(RenameOptions {
                     prepare_provider: Some(false),
                     work_done_progress_options: WorkDoneProgressOptions::default(),
@@ -103,9 +412,19 @@ impl LanguageServer for Backend {
 
     async fn initialized(&self, _: InitializedParams) {
         info!("Lsp initialized");
+
+        self.register_file_watchers().await;
+        self.index_workspace().await;
     }
 
     async fn shutdown(&self) -> Result<()> {
+        let _ = self
+            .guard
+            .write("shutdown", None, |workspace| {
+                workspace.remove_bundled_includes()
+            })
+            .await;
+
         info!("Lsp stopped");
         Ok(())
     }
@@ -114,12 +433,19 @@ impl LanguageServer for Backend {
         let doc = params.text_document;
         info!("Opening file: {}", doc.uri);
 
-        let mut diagnostics = {
-            let mut workspace = self.workspace.write().unwrap();
-            (*workspace).add_file(doc.uri.clone(), &doc.text);
-
-            (*workspace).get_full_diagnostics(doc.uri.clone())
-        };
+        let _ = self
+            .analysis
+            .update("opening a file", Some(&doc.uri), |workspace| {
+                workspace.add_file(doc.uri.clone(), &doc.text, doc.version)
+            })
+            .await;
+        let mut diagnostics = self
+            .analysis
+            .compute_diagnostics(&doc.uri)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
 
         let mut plugin_result: PluginsResult = self
             .plugin_manager
@@ -130,35 +456,67 @@ impl LanguageServer for Backend {
         diagnostics.append(&mut plugin_result.diagnostic);
 
         self.client
-            .publish_diagnostics(doc.uri, diagnostics, None)
+            .publish_diagnostics(doc.uri, diagnostics, Some(doc.version))
             .await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        let diagnostics = {
-            let mut workspace = self.workspace.write().unwrap();
-            (*workspace).update_file(params.text_document.uri.clone(), params.content_changes);
+        let doc = params.text_document;
 
-            (*workspace).get_quick_diagnostics(params.text_document.uri.clone())
-        };
+        let _ = self
+            .guard
+            .write("editing", Some(&doc.uri), |workspace| {
+                workspace.update_file(doc.uri.clone(), doc.version, params.content_changes)
+            })
+            .await;
 
-        self.client
-            .publish_diagnostics(params.text_document.uri, diagnostics, None)
+        self.analysis.schedule(doc.uri, doc.version);
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        info!("Closing file: {}", uri);
+        self.analysis.cancel(&uri);
+
+        let _ = self
+            .analysis
+            .update("closing a file", Some(&uri), |workspace| {
+                workspace.close_file(uri.clone())
+            })
             .await;
+
+        self.client.publish_diagnostics(uri, vec![], None).await;
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
-        let mut diagnostics = {
-            let workspace = self.workspace.read().unwrap();
+        let uri = params.text_document.uri;
 
-            (*workspace).get_full_diagnostics(params.text_document.uri.clone())
-        };
+        let _ = self
+            .analysis
+            .update("analysis", Some(&uri), |workspace| {
+                workspace.analyze_file(&uri)
+            })
+            .await;
+        let version = self
+            .guard
+            .read("diagnostics", Some(&uri), |workspace| {
+                workspace.get_version(&uri)
+            })
+            .await
+            .unwrap_or_default();
+        let mut diagnostics = self
+            .analysis
+            .compute_diagnostics(&uri)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
 
         let mut plugin_result: PluginsResult = self
             .plugin_manager
             .write()
             .unwrap()
-            .run_plugins(params.text_document.uri.clone(), OnState::Save);
+            .run_plugins(uri.clone(), OnState::Save);
 
         diagnostics.append(&mut plugin_result.diagnostic);
 
@@ -169,7 +527,7 @@ impl LanguageServer for Backend {
         }
 
         self.client
-            .publish_diagnostics(params.text_document.uri, diagnostics, None)
+            .publish_diagnostics(uri, diagnostics, version)
             .await;
     }
 
@@ -178,12 +536,17 @@ impl LanguageServer for Backend {
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
         let uri = params.text_document_position_params.text_document.uri;
-
-        let maybe_location = {
-            let workspace = self.workspace.read().unwrap();
-
-            (*workspace).get_definition_location(uri, params.text_document_position_params.position)
-        };
+        self.analysis.update_symbols(&uri).await;
+
+        let maybe_location = self
+            .guard
+            .read("go to definition", Some(&uri), |workspace| {
+                workspace.get_definition_location(
+                    uri.clone(),
+                    params.text_document_position_params.position,
+                )
+            })
+            .await?;
 
         if let Some(location) = maybe_location {
             Ok(Some(GotoDefinitionResponse::Scalar(location)))
@@ -193,14 +556,15 @@ impl LanguageServer for Backend {
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-        let maybe_hover_info = {
-            let workspace = self.workspace.read().unwrap();
+        let uri = params.text_document_position_params.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
 
-            (*workspace).get_hover_info(
-                params.text_document_position_params.text_document.uri,
-                params.text_document_position_params.position,
-            )
-        };
+        let maybe_hover_info = self
+            .guard
+            .read("hover", Some(&uri), |workspace| {
+                workspace.get_hover_info(uri.clone(), params.text_document_position_params.position)
+            })
+            .await?;
 
         if let Some(hover_info) = maybe_hover_info {
             Ok(Some(Hover {
@@ -212,51 +576,168 @@ impl LanguageServer for Backend {
         }
     }
 
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
+
+        self.guard
+            .read("signature help", Some(&uri), |workspace| {
+                workspace
+                    .get_signature_help(uri.clone(), params.text_document_position_params.position)
+            })
+            .await
+    }
+
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
-        let response = {
-            let workspace = self.workspace.read().unwrap();
+        let uri = params.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
 
-            Ok((*workspace).get_semantic_tokens(params.text_document.uri))
-        };
+        let tokens = self
+            .guard
+            .read("semantic highlighting", Some(&uri), |workspace| {
+                workspace.get_semantic_tokens(uri.clone())
+            })
+            .await?;
 
-        response
+        Ok(tokens.map(SemanticTokensResult::Tokens))
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        let uri = params.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
+
+        self.guard
+            .read("semantic highlighting", Some(&uri), |workspace| {
+                workspace.get_semantic_tokens_delta(uri.clone(), &params.previous_result_id)
+            })
+            .await
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        let uri = params.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
+
+        let tokens = self
+            .guard
+            .read("semantic highlighting", Some(&uri), |workspace| {
+                workspace.get_semantic_tokens_range(uri.clone(), params.range)
+            })
+            .await?;
+
+        Ok(tokens.map(SemanticTokensRangeResult::Tokens))
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
-        let completion_list = {
-            let workspace = self.workspace.read().unwrap();
+        let uri = params.text_document_position.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
+
+        let completion_list = self
+            .guard
+            .read("completion", Some(&uri), |workspace| {
+                workspace
+                    .get_completion(
+                        uri.clone(),
+                        params.text_document_position.position,
+                        params.context,
+                    )
+                    .unwrap_or_default()
+            })
+            .await?;
+
+        Ok(Some(CompletionResponse::Array(completion_list)))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
 
-            (*workspace)
-                .get_completion(
-                    params.text_document_position.text_document.uri,
+        self.guard
+            .write("rename", Some(&uri), |workspace| {
+                workspace.rename_symbol(
+                    uri.clone(),
                     params.text_document_position.position,
-                    params.context,
+                    params.new_name,
                 )
-                .unwrap_or_default()
-        };
+            })
+            .await
+    }
 
-        Ok(Some(CompletionResponse::Array(completion_list)))
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
+
+        self.guard
+            .read("references", Some(&uri), |workspace| {
+                workspace.get_references(
+                    uri.clone(),
+                    params.text_document_position.position,
+                    params.context.include_declaration,
+                )
+            })
+            .await
     }
 
-    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
-        let response = {
-            let mut workspace = self.workspace.write().unwrap();
-
-            Ok((*workspace).rename_symbol(
-                params.text_document_position.text_document.uri,
-                params.text_document_position.position,
-                params.new_name,
-            ))
-        };
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri;
+        self.analysis.update_symbols(&uri).await;
+
+        self.guard
+            .read("document outline", Some(&uri), |workspace| {
+                workspace
+                    .get_document_symbols(uri.clone())
+                    .map(DocumentSymbolResponse::Nested)
+            })
+            .await
+    }
 
-        response
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        self.guard
+            .read("workspace symbol search", None, |workspace| {
+                Some(workspace.get_workspace_symbols(&params.query))
+            })
+            .await
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            let _ = self
+                .analysis
+                .update(
+                    "file reloading",
+                    Some(&change.uri),
+                    |workspace| match change.typ {
+                        FileChangeType::CREATED | FileChangeType::CHANGED => {
+                            workspace.reload_file(change.uri.clone())
+                        }
+                        FileChangeType::DELETED => workspace.remove_file(change.uri.clone()),
+                        _ => vec![],
+                    },
+                )
+                .await;
+        }
     }
 
     async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
-        let mut workspace = self.workspace.write().unwrap();
-        (*workspace).update_settings(params.settings);
+        let _ = self
+            .analysis
+            .update("settings update", None, |workspace| {
+                workspace.update_settings(params.settings)
+            })
+            .await;
     }
 }
@@ -3,7 +3,10 @@ extern crate lazy_static;
 
 mod features;
 mod file;
+mod guard;
+mod include;
 mod language_def;
+mod line_index;
 mod lsp_mappings;
 mod metadata;
 mod plugin_manager;
@@ -14,14 +17,23 @@ mod workspace;
 #[macro_use]
 extern crate log;
 
-pub async fn start_server(language_def: &str, ts_language: tree_sitter::Language) {
+/// Starts the language server on stdio.
+///
+/// `bundled_includes` lists `(file name, content)` pairs of library files shipped
+/// with the server, used when an include directive can't be resolved on disk.
+pub async fn start_server(
+    language_def: &str,
+    ts_language: tree_sitter::Language,
+    bundled_includes: &'static [(&'static str, &'static str)],
+) {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
     language_def::LanguageDefinition::load(language_def);
 
-    let (service, socket) =
-        tower_lsp::LspService::new(|client| language_server::Backend::init(client, ts_language));
+    let (service, socket) = tower_lsp::LspService::new(|client| {
+        language_server::Backend::init(client, ts_language, bundled_includes)
+    });
     tower_lsp::Server::new(stdin, stdout, socket)
         .serve(service)
         .await;
@@ -0,0 +1,256 @@
+use tokio::sync::OnceCell;
+use tower_lsp::lsp_types::{ClientCapabilities, Position, PositionEncodingKind, Range};
+use tree_sitter::Point;
+
+static ENCODING: OnceCell<PositionEncoding> = OnceCell::const_new();
+
+/// Unit in which the `character` of an LSP position is counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+}
+
+impl PositionEncoding {
+    /// Picks UTF-8 when the client supports it, since it matches the byte
+    /// offsets of the syntax tree, and otherwise UTF-16, which every client supports.
+    pub fn negotiate(capabilities: &ClientCapabilities) -> PositionEncoding {
+        let supports_utf8 = capabilities
+            .general
+            .as_ref()
+            .and_then(|general| general.position_encodings.as_ref())
+            .is_some_and(|encodings| encodings.contains(&PositionEncodingKind::UTF8));
+
+        if supports_utf8 {
+            PositionEncoding::Utf8
+        } else {
+            PositionEncoding::Utf16
+        }
+    }
+
+    /// Sets the encoding used for the session. Only the first call has an effect.
+    pub fn set(self) {
+        let _ = ENCODING.set(self);
+    }
+
+    pub fn get() -> PositionEncoding {
+        ENCODING.get().copied().unwrap_or_default()
+    }
+
+    pub fn kind(&self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+        }
+    }
+
+    fn len(&self, c: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => c.len_utf8(),
+            PositionEncoding::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+/// Converts between LSP positions and byte offsets of a text.
+///
+/// Lines end with `\n`, optionally preceded by `\r`, like the rows of tree-sitter.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    encoding: PositionEncoding,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        LineIndex::with_encoding(text, PositionEncoding::get())
+    }
+
+    pub fn with_encoding(text: &str, encoding: PositionEncoding) -> LineIndex {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        LineIndex {
+            line_starts,
+            encoding,
+        }
+    }
+
+    /// Byte offset of `position` in `text`. Positions past the end of a line
+    /// are clamped to the line break and positions inside a character move to its end.
+    pub fn offset(&self, text: &str, position: Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return text.len();
+        };
+        let line_end = self.line_end(text, position.line as usize);
+
+        let mut units = 0;
+        for (i, c) in text[line_start..line_end].char_indices() {
+            if units >= position.character as usize {
+                return line_start + i;
+            }
+            units += self.encoding.len(c);
+        }
+
+        line_end
+    }
+
+    /// LSP position of the byte `offset` in `text`.
+    pub fn position(&self, text: &str, offset: usize) -> Position {
+        let offset = offset.min(text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let character = text[self.line_starts[line]..offset]
+            .chars()
+            .map(|c| self.encoding.len(c))
+            .sum::<usize>();
+
+        Position::new(line as u32, character as u32)
+    }
+
+    /// Tree-sitter point (row and byte column) of the byte `offset`.
+    pub fn point(&self, offset: usize) -> Point {
+        let row = self.line_starts.partition_point(|&start| start <= offset) - 1;
+
+        Point {
+            row,
+            column: offset - self.line_starts[row],
+        }
+    }
+
+    pub fn range(&self, text: &str, range: tree_sitter::Range) -> Range {
+        Range::new(
+            self.position(text, range.start_byte),
+            self.position(text, range.end_byte),
+        )
+    }
+
+    /// Length of a line of `text` without its line break, or 0 past the last line.
+    pub fn line_length(&self, text: &str, line: u32) -> u32 {
+        if line as usize >= self.line_starts.len() {
+            return 0;
+        }
+
+        self.position(text, self.line_end(text, line as usize))
+            .character
+    }
+
+    fn line_end(&self, text: &str, line: usize) -> usize {
+        match self.line_starts.get(line + 1) {
+            Some(next_start) if text[..next_start - 1].ends_with('\r') => next_start - 2,
+            Some(next_start) => next_start - 1,
+            None => text.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tree_sitter::Point;
+
+    use super::{LineIndex, PositionEncoding};
+
+    const TEXT: &str = "// Entête\r\nbit<8> x; // 😀 é\r\n\nend";
+
+    #[test]
+    fn test_position() {
+        let utf16 = LineIndex::with_encoding(TEXT, PositionEncoding::Utf16);
+        let utf8 = LineIndex::with_encoding(TEXT, PositionEncoding::Utf8);
+        let end = TEXT.find("end").unwrap();
+        let accent = TEXT.find('é').unwrap();
+
+        assert_eq!(utf16.position(TEXT, end), Position::new(3, 0));
+        assert_eq!(utf16.position(TEXT, accent), Position::new(1, 16));
+        assert_eq!(utf8.position(TEXT, accent), Position::new(1, 18));
+        assert_eq!(utf16.position(TEXT, TEXT.len()), Position::new(3, 3));
+    }
+
+    #[test]
+    fn test_line_length() {
+        let utf16 = LineIndex::with_encoding(TEXT, PositionEncoding::Utf16);
+        let utf8 = LineIndex::with_encoding(TEXT, PositionEncoding::Utf8);
+
+        assert_eq!(utf16.line_length(TEXT, 0), 9);
+        assert_eq!(utf8.line_length(TEXT, 0), 10);
+        assert_eq!(utf16.line_length(TEXT, 1), 17);
+        assert_eq!(utf16.line_length(TEXT, 2), 0);
+        assert_eq!(utf16.line_length(TEXT, 3), 3);
+        assert_eq!(utf16.line_length(TEXT, 4), 0);
+    }
+
+    #[test]
+    fn test_offset() {
+        let utf16 = LineIndex::with_encoding(TEXT, PositionEncoding::Utf16);
+        let utf8 = LineIndex::with_encoding(TEXT, PositionEncoding::Utf8);
+
+        assert_eq!(
+            utf16.offset(TEXT, Position::new(1, 16)),
+            TEXT.find('é').unwrap()
+        );
+        assert_eq!(
+            utf8.offset(TEXT, Position::new(1, 18)),
+            TEXT.find('é').unwrap()
+        );
+        // Past the end of the line, before the CRLF
+        assert_eq!(
+            utf16.offset(TEXT, Position::new(0, 40)),
+            TEXT.find('\r').unwrap()
+        );
+        assert_eq!(utf16.offset(TEXT, Position::new(9, 0)), TEXT.len());
+        // Inside the surrogate pair of the emoji
+        assert_eq!(
+            utf16.offset(TEXT, Position::new(1, 14)),
+            TEXT.find('😀').unwrap() + '😀'.len_utf8()
+        );
+    }
+
+    #[test]
+    fn test_point() {
+        let index = LineIndex::new(TEXT);
+
+        assert_eq!(
+            index.point(TEXT.find('é').unwrap()),
+            Point { row: 1, column: 18 }
+        );
+        assert_eq!(index.point(TEXT.len()), Point { row: 3, column: 3 });
+    }
+
+    #[test]
+    fn test_round_trip_random_text() {
+        let alphabet = ['a', ' ', 'é', '😀', '\n', '\r', '\t', '€'];
+        let mut seed = 0x2545_f491_u64;
+
+        for _ in 0..200 {
+            let text: String = (0..40)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    alphabet[(seed % alphabet.len() as u64) as usize]
+                })
+                .collect();
+
+            for encoding in [PositionEncoding::Utf8, PositionEncoding::Utf16] {
+                let index = LineIndex::with_encoding(&text, encoding);
+
+                for (offset, _) in text
+                    .char_indices()
+                    .filter(|&(i, _)| !text[i..].starts_with('\n') || !text[..i].ends_with('\r'))
+                {
+                    let position = index.position(&text, offset);
+                    assert_eq!(index.offset(&text, position), offset, "{:?}", text);
+
+                    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+                    let prefix = &text[line_start..offset];
+                    let expected = match encoding {
+                        PositionEncoding::Utf8 => prefix.len(),
+                        PositionEncoding::Utf16 => prefix.encode_utf16().count(),
+                    };
+                    assert_eq!(position.character as usize, expected);
+                }
+            }
+        }
+    }
+}
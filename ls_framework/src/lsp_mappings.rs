@@ -1,5 +1,5 @@
 use serde::Deserialize;
-use tower_lsp::lsp_types::{self, CompletionItemKind};
+use tower_lsp::lsp_types::{self, CompletionItemKind, SymbolKind};
 
 #[derive(Debug, Deserialize, Clone)]
 pub enum SymbolCompletionType {
@@ -118,3 +118,97 @@ impl HighlightType {
         }
     }
 }
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum HighlightModifier {
+    Declaration,
+    Definition,
+    Readonly,
+    Static,
+    Deprecated,
+    Abstract,
+    Async,
+    Modification,
+    Documentation,
+    DefaultLibrary,
+}
+
+impl HighlightModifier {
+    pub fn get(&self) -> lsp_types::SemanticTokenModifier {
+        match self {
+            HighlightModifier::Declaration => lsp_types::SemanticTokenModifier::DECLARATION,
+            HighlightModifier::Definition => lsp_types::SemanticTokenModifier::DEFINITION,
+            HighlightModifier::Readonly => lsp_types::SemanticTokenModifier::READONLY,
+            HighlightModifier::Static => lsp_types::SemanticTokenModifier::STATIC,
+            HighlightModifier::Deprecated => lsp_types::SemanticTokenModifier::DEPRECATED,
+            HighlightModifier::Abstract => lsp_types::SemanticTokenModifier::ABSTRACT,
+            HighlightModifier::Async => lsp_types::SemanticTokenModifier::ASYNC,
+            HighlightModifier::Modification => lsp_types::SemanticTokenModifier::MODIFICATION,
+            HighlightModifier::Documentation => lsp_types::SemanticTokenModifier::DOCUMENTATION,
+            HighlightModifier::DefaultLibrary => lsp_types::SemanticTokenModifier::DEFAULT_LIBRARY,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub enum OutlineType {
+    File,
+    Module,
+    Namespace,
+    Package,
+    Class,
+    Method,
+    Property,
+    Field,
+    Constructor,
+    Enum,
+    Interface,
+    Function,
+    Variable,
+    Constant,
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Key,
+    Null,
+    EnumMember,
+    Struct,
+    Event,
+    Operator,
+    TypeParameter,
+}
+
+impl OutlineType {
+    pub fn get(&self) -> SymbolKind {
+        match self {
+            OutlineType::File => SymbolKind::FILE,
+            OutlineType::Module => SymbolKind::MODULE,
+            OutlineType::Namespace => SymbolKind::NAMESPACE,
+            OutlineType::Package => SymbolKind::PACKAGE,
+            OutlineType::Class => SymbolKind::CLASS,
+            OutlineType::Method => SymbolKind::METHOD,
+            OutlineType::Property => SymbolKind::PROPERTY,
+            OutlineType::Field => SymbolKind::FIELD,
+            OutlineType::Constructor => SymbolKind::CONSTRUCTOR,
+            OutlineType::Enum => SymbolKind::ENUM,
+            OutlineType::Interface => SymbolKind::INTERFACE,
+            OutlineType::Function => SymbolKind::FUNCTION,
+            OutlineType::Variable => SymbolKind::VARIABLE,
+            OutlineType::Constant => SymbolKind::CONSTANT,
+            OutlineType::String => SymbolKind::STRING,
+            OutlineType::Number => SymbolKind::NUMBER,
+            OutlineType::Boolean => SymbolKind::BOOLEAN,
+            OutlineType::Array => SymbolKind::ARRAY,
+            OutlineType::Object => SymbolKind::OBJECT,
+            OutlineType::Key => SymbolKind::KEY,
+            OutlineType::Null => SymbolKind::NULL,
+            OutlineType::EnumMember => SymbolKind::ENUM_MEMBER,
+            OutlineType::Struct => SymbolKind::STRUCT,
+            OutlineType::Event => SymbolKind::EVENT,
+            OutlineType::Operator => SymbolKind::OPERATOR,
+            OutlineType::TypeParameter => SymbolKind::TYPE_PARAMETER,
+        }
+    }
+}
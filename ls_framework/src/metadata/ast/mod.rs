@@ -1,4 +1,7 @@
 mod rules_translator;
 mod tree;
 
-pub use tree::{Ast, Node, NodeKind, TypeDecType, VisitNode, Visitable};
+pub use tree::{
+    Ast, Direction, Literal, Node, NodeKind, Operator, SourceEdit, TypeDecType, VisitNode,
+    Visitable,
+};
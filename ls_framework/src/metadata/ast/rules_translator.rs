@@ -1,14 +1,20 @@
+use std::collections::HashSet;
+
 use indextree::{Arena, NodeId};
+use tower_lsp::lsp_types::Range;
 
-use super::{tree::Translator, Ast, Node, NodeKind};
+use super::{tree::Translator, Ast, Node, NodeKind, Visitable};
 use crate::{
     language_def::{Child, DirectOrRule, LanguageDefinition, Rule, Symbol, TreesitterNodeQuery},
+    line_index::LineIndex,
     lsp_mappings::HighlightType,
+    utils,
 };
 
 pub struct RulesTranslator {
     arena: Arena<Node>,
     source_code: String,
+    line_index: LineIndex,
     tree: tree_sitter::Tree,
     language_def: &'static LanguageDefinition,
 }
@@ -20,11 +26,88 @@ impl Translator for RulesTranslator {
 
         Ast::initialize(translator.arena, root_id)
     }
+
+    fn retranslate(
+        ast: &mut Ast,
+        source_code: String,
+        syntax_tree: tree_sitter::Tree,
+        changed_ranges: Vec<Range>,
+    ) {
+        let root_id = ast.visit_root().get_id();
+        let mut translator = RulesTranslator::new(source_code, syntax_tree.clone());
+        translator.arena = std::mem::take(ast.get_arena());
+
+        let ts_root = syntax_tree.root_node();
+        let mut cursor = ts_root.walk();
+        let ts_children: Vec<tree_sitter::Node> = ts_root.children(&mut cursor).collect();
+
+        // A replaced node can cover syntax nodes outside of the changed ranges
+        // (and the other way around), so the ranges grow until both sides agree.
+        let mut ranges = changed_ranges;
+        let mut selected: HashSet<usize> = HashSet::new();
+        let mut removed: Vec<NodeId> = vec![];
+        loop {
+            let mut stable = true;
+
+            for (i, ts_node) in ts_children.iter().enumerate() {
+                let range = translator
+                    .line_index
+                    .range(&translator.source_code, ts_node.range());
+                if !selected.contains(&i) && overlaps_any(range, &ranges) {
+                    selected.insert(i);
+                    ranges.push(range);
+                    stable = false;
+                }
+            }
+
+            for id in root_id.children(&translator.arena) {
+                let range = translator.arena.get(id).unwrap().get().range;
+                if !removed.contains(&id) && overlaps_any(range, &ranges) {
+                    removed.push(id);
+                    ranges.push(range);
+                    stable = false;
+                }
+            }
+
+            if stable {
+                break;
+            }
+        }
+
+        for id in removed {
+            id.remove_subtree(&mut translator.arena);
+        }
+
+        let root = translator.arena.get_mut(root_id).unwrap().get_mut();
+        root.range = translator
+            .line_index
+            .range(&translator.source_code, ts_root.range());
+        root.content = utils::get_node_text(&ts_root, &translator.source_code);
+
+        let language_def = translator.language_def;
+        let root_rule = language_def.rule_with_name("Root").unwrap();
+        for child in root_rule
+            .children
+            .iter()
+            .chain(&language_def.global_ast_rules)
+        {
+            translator.query_parse_child(&ts_children, child, root_id, Some(&selected));
+        }
+
+        *ast.get_arena() = translator.arena;
+    }
+}
+
+fn overlaps_any(range: Range, ranges: &[Range]) -> bool {
+    ranges
+        .iter()
+        .any(|other| range.start <= other.end && other.start <= range.end)
 }
 
 impl RulesTranslator {
     fn new(source_code: String, syntax_tree: tree_sitter::Tree) -> RulesTranslator {
         RulesTranslator {
+            line_index: LineIndex::new(&source_code),
             source_code,
             arena: Arena::new(),
             tree: syntax_tree,
@@ -54,11 +137,11 @@ impl RulesTranslator {
         // }
 
         for child in current_rule.children.iter() {
-            self.query_parse_child(&children, child, current_node_id);
+            self.query_parse_child(&children, child, current_node_id, None);
         }
 
         for child in &LanguageDefinition::get().global_ast_rules {
-            self.query_parse_child(&children, child, current_node_id);
+            self.query_parse_child(&children, child, current_node_id, None);
         }
 
         current_node_id
@@ -69,10 +152,15 @@ impl RulesTranslator {
         children: &[tree_sitter::Node],
         child: &Child,
         current_node_id: NodeId,
+        selected: Option<&HashSet<usize>>,
     ) {
         let (query, node_or_rule) = (&child.query, &child.rule);
 
-        for (i, ts_node) in children.iter().enumerate() {
+        'children: for (i, ts_node) in children.iter().enumerate() {
+            if selected.is_some_and(|selected| !selected.contains(&i)) {
+                continue;
+            }
+
             let target_node = if let TreesitterNodeQuery::Path(path) = query {
                 if path.is_empty() {
                     continue;
@@ -111,7 +199,7 @@ impl RulesTranslator {
                     if let Some((_, node)) = found {
                         current_ts_node = node;
                     } else {
-                        continue;
+                        continue 'children;
                     }
                 }
                 current_ts_node
@@ -163,6 +251,7 @@ impl RulesTranslator {
             kind,
             syntax_node,
             &self.source_code,
+            &self.line_index,
             symbol,
             semantic_token_type,
         ))
@@ -177,6 +266,7 @@ impl RulesTranslator {
             NodeKind::Error(message),
             syntax_node,
             &self.source_code,
+            &self.line_index,
             Symbol::None,
             None,
         ))
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use indextree::{Arena, NodeId};
 use serde::Deserialize;
@@ -8,6 +9,7 @@ use tower_lsp::lsp_types::{Position, Range};
 
 use crate::{
     language_def::{self, Symbol},
+    line_index::LineIndex,
     lsp_mappings::HighlightType,
     metadata::symbol_table::SymbolId,
     utils,
@@ -15,17 +17,84 @@ use crate::{
 
 use super::rules_translator::RulesTranslator;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Operator {
     Add,
     Subtract,
     Multiply,
     Divide,
     Modulo,
-    //...
+    SaturatingAdd,
+    SaturatingSubtract,
+    ShiftLeft,
+    ShiftRight,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Complement,
+    Concat,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Not,
+}
+
+const OPERATORS: [(Operator, &str); 23] = [
+    (Operator::Add, "+"),
+    (Operator::Subtract, "-"),
+    (Operator::Multiply, "*"),
+    (Operator::Divide, "/"),
+    (Operator::Modulo, "%"),
+    (Operator::SaturatingAdd, "|+|"),
+    (Operator::SaturatingSubtract, "|-|"),
+    (Operator::ShiftLeft, "<<"),
+    (Operator::ShiftRight, ">>"),
+    (Operator::BitAnd, "&"),
+    (Operator::BitOr, "|"),
+    (Operator::BitXor, "^"),
+    (Operator::Complement, "~"),
+    (Operator::Concat, "++"),
+    (Operator::Equal, "=="),
+    (Operator::NotEqual, "!="),
+    (Operator::Less, "<"),
+    (Operator::LessEqual, "<="),
+    (Operator::Greater, ">"),
+    (Operator::GreaterEqual, ">="),
+    (Operator::And, "&&"),
+    (Operator::Or, "||"),
+    (Operator::Not, "!"),
+];
+
+impl Operator {
+    pub fn parse(text: &str) -> Option<Operator> {
+        OPERATORS
+            .iter()
+            .find(|(_, symbol)| *symbol == text.trim())
+            .map(|(operator, _)| *operator)
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let (_, symbol) = OPERATORS.iter().find(|(op, _)| op == self).unwrap();
+        fmt.write_str(symbol)
+    }
+}
+
+/// Kinds of literal nodes, named by the language definition.
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+pub enum Literal {
+    Integer,
+    Bool,
+    String,
 }
 
-#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
 pub enum TypeDecType {
     TypeDef,
     HeaderType,
@@ -37,13 +106,34 @@ pub enum TypeDecType {
     Package,
 }
 
-#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
 pub enum Direction {
     In,
     Out,
     InOut,
 }
 
+impl Direction {
+    pub fn parse(text: &str) -> Option<Direction> {
+        match text.trim() {
+            "in" => Some(Direction::In),
+            "out" => Some(Direction::Out),
+            "inout" => Some(Direction::InOut),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+            Direction::InOut => "inout",
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 pub enum NodeKind {
     Node(String),
@@ -71,12 +161,13 @@ impl Node {
         kind: NodeKind,
         syntax_node: &tree_sitter::Node,
         source_code: &str,
+        line_index: &LineIndex,
         symbol: Symbol,
         semantic_token_type: Option<HighlightType>,
     ) -> Node {
         Node {
             kind,
-            range: utils::ts_range_to_lsp_range(syntax_node.range()),
+            range: line_index.range(source_code, syntax_node.range()),
             content: utils::get_node_text(syntax_node, source_code),
             symbol,
             semantic_token_type,
@@ -169,12 +260,52 @@ impl Visitable for VisitNode<'_> {
 
 pub trait Translator {
     fn translate(source_code: String, syntax_tree: tree_sitter::Tree) -> Ast;
+    fn retranslate(
+        ast: &mut Ast,
+        source_code: String,
+        syntax_tree: tree_sitter::Tree,
+        changed_ranges: Vec<Range>,
+    );
+}
+
+/// An edit of the source code: `old_range` was replaced by text ending at `new_end`.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceEdit {
+    pub old_range: Range,
+    pub new_end: Position,
+}
+
+impl SourceEdit {
+    pub fn new_range(&self) -> Range {
+        Range::new(self.old_range.start, self.new_end)
+    }
+
+    /// Moves a position following the edit to where it is after the edit.
+    fn shift(&self, position: Position) -> Position {
+        let old_end = self.old_range.end;
+
+        if position.line == old_end.line {
+            Position::new(
+                self.new_end.line,
+                position.character - old_end.character + self.new_end.character,
+            )
+        } else {
+            Position::new(
+                position.line - old_end.line + self.new_end.line,
+                position.character,
+            )
+        }
+    }
 }
 
+static NEXT_AST_ID: AtomicUsize = AtomicUsize::new(0);
+
 #[derive(Debug, Clone)]
 pub struct Ast {
     arena: Arena<Node>,
     root_id: NodeId,
+    /// Identifies the translation the nodes come from, edits in place keep it.
+    id: usize,
 }
 
 impl fmt::Display for Ast {
@@ -185,7 +316,16 @@ impl fmt::Display for Ast {
 
 impl Ast {
     pub fn initialize(arena: Arena<Node>, root_id: NodeId) -> Ast {
-        Ast { arena, root_id }
+        Ast {
+            arena,
+            root_id,
+            id: NEXT_AST_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Ids of the nodes of ASTs with different ids can't be compared.
+    pub fn get_id(&self) -> usize {
+        self.id
     }
 
     pub fn new(source_code: &str, syntax_tree: tree_sitter::Tree) -> Option<Ast> {
@@ -195,6 +335,37 @@ impl Ast {
         ))
     }
 
+    /// Updates the AST after an edit, only translating again the top-level
+    /// nodes overlapping the edit or the ranges where the syntax tree changed.
+    pub fn apply_edit(
+        &mut self,
+        source_code: &str,
+        syntax_tree: tree_sitter::Tree,
+        edit: SourceEdit,
+        mut changed_ranges: Vec<Range>,
+    ) {
+        for node in self.arena.iter_mut().filter(|node| !node.is_removed()) {
+            let range = &mut node.get_mut().range;
+
+            if range.start >= edit.old_range.end {
+                range.start = edit.shift(range.start);
+                range.end = edit.shift(range.end);
+            } else if range.end >= edit.old_range.start {
+                // Overlapping nodes are translated again, they only need to
+                // keep overlapping the new range
+                range.start = range.start.min(edit.old_range.start);
+                range.end = if range.end >= edit.old_range.end {
+                    edit.shift(range.end)
+                } else {
+                    edit.new_end
+                };
+            }
+        }
+
+        changed_ranges.push(edit.new_range());
+        RulesTranslator::retranslate(self, source_code.to_string(), syntax_tree, changed_ranges);
+    }
+
     pub fn visit_root(&self) -> VisitNode {
         VisitNode::new(&self.arena, self.root_id)
     }
@@ -1,30 +1,41 @@
 use core::fmt;
+use std::sync::Arc;
 
-use crate::metadata::ast::VisitNode;
+use tower_lsp::lsp_types::Range;
+
+use crate::metadata::ast::{SourceEdit, VisitNode};
 
 use super::Ast;
 
 pub trait AstEditor {
     fn update(&mut self, content: &str, syntax_tree: tree_sitter::Tree);
+    fn apply_edit(
+        &mut self,
+        content: &str,
+        syntax_tree: tree_sitter::Tree,
+        edit: SourceEdit,
+        changed_ranges: Vec<Range>,
+    );
 }
 
 pub trait AstQuery {
     fn visit_root(&self) -> VisitNode;
 }
 
+/// The AST is shared by the clones of the manager until one of them changes it.
 #[derive(Debug, Clone)]
 pub struct AstManager {
-    pub ast: Ast,
+    ast: Arc<Ast>,
 }
 
 impl AstManager {
     pub fn new(source_code: &str, tree: tree_sitter::Tree) -> AstManager {
-        let ast = Ast::new(source_code, tree).unwrap();
+        let ast = Arc::new(Ast::new(source_code, tree).unwrap());
         AstManager { ast }
     }
 
     pub fn get_ast(&mut self) -> &mut Ast {
-        &mut self.ast
+        Arc::make_mut(&mut self.ast)
     }
 }
 
@@ -44,4 +55,15 @@ impl AstEditor for AstManager {
     fn update(&mut self, content: &str, syntax_tree: tree_sitter::Tree) {
         *self = AstManager::new(content, syntax_tree);
     }
+
+    fn apply_edit(
+        &mut self,
+        content: &str,
+        syntax_tree: tree_sitter::Tree,
+        edit: SourceEdit,
+        changed_ranges: Vec<Range>,
+    ) {
+        self.get_ast()
+            .apply_edit(content, syntax_tree, edit, changed_ranges);
+    }
 }
@@ -0,0 +1,377 @@
+use std::fmt;
+
+use tower_lsp::lsp_types::{Position, Range};
+
+use super::{NodeKind, Symbol, SymbolTableQuery, VisitNode, Visitable};
+use crate::language_def::{self, Calls};
+use crate::utils::compact;
+
+/// A call, or an instantiation calling a constructor.
+pub struct Call {
+    /// Text naming the callable, e.g. `pkt.extract`.
+    pub callee: String,
+    pub range: Range,
+    pub arguments: Vec<Argument>,
+    /// Callables the call can run, several when they are overloaded.
+    pub signatures: Vec<Signature>,
+}
+
+pub struct Argument {
+    /// Name of the parameter the argument is passed to, when given.
+    pub name: Option<String>,
+    pub text: String,
+    pub range: Range,
+}
+
+pub struct Signature {
+    pub callable: Symbol,
+    pub parameters: Vec<Symbol>,
+    /// Type parameters of the callable's owner, with the types given by the instance,
+    /// e.g. `T` and `bit<32>` for `register<bit<32>>`.
+    pub type_arguments: Vec<(String, String)>,
+}
+
+impl Call {
+    /// The signature the arguments are passed to, overloads being told apart by
+    /// their number of parameters.
+    pub fn get_signature(&self) -> Option<&Signature> {
+        match self.signatures.as_slice() {
+            [signature] => Some(signature),
+            signatures => signatures
+                .iter()
+                .find(|signature| signature.parameters.len() == self.arguments.len()),
+        }
+    }
+}
+
+impl Signature {
+    /// Parameter an argument is passed to, by its name or its position.
+    pub fn get_parameter(&self, index: usize, argument: &Argument) -> Option<&Symbol> {
+        match &argument.name {
+            Some(name) => self
+                .parameters
+                .iter()
+                .find(|parameter| parameter.get_name() == *name),
+            None => self.parameters.get(index),
+        }
+    }
+
+    /// A parameter as written, with the type arguments of the owner, e.g. `out bit<32> result`.
+    pub fn format_parameter(&self, parameter: &Symbol) -> String {
+        let mut text = String::new();
+        if let Some(direction) = parameter.get_direction() {
+            text.push_str(&format!("{} ", direction));
+        }
+        if let Some(type_name) = self.get_parameter_type(parameter) {
+            text.push_str(&format!("{} ", type_name));
+        }
+        text.push_str(&parameter.get_name());
+
+        text
+    }
+
+    /// Type of a parameter, with the type arguments of the owner substituted.
+    pub fn get_parameter_type(&self, parameter: &Symbol) -> Option<String> {
+        let type_name = parameter.get_type_name()?.trim();
+
+        Some(
+            self.type_arguments
+                .iter()
+                .find(|(name, _)| name == type_name)
+                .map_or(type_name, |(_, value)| value.as_str())
+                .to_string(),
+        )
+    }
+}
+
+/// The signature as written, e.g. `read(out bit<32> result, in bit<32> index)`.
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parameters: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|parameter| self.format_parameter(parameter))
+            .collect();
+
+        write!(f, "{}({})", self.callable.get_name(), parameters.join(", "))
+    }
+}
+
+/// Resolves a node of a call or instantiation rule, with its arguments.
+pub fn resolve_call<Q: SymbolTableQuery>(
+    symbol_table: &Q,
+    config: &Calls,
+    node: &VisitNode,
+) -> Option<Call> {
+    let (_, _, arguments_rule) = find_rule(config, node)?.1;
+    let arguments = node.get_child_of_kind(NodeKind::Node(arguments_rule.clone()))?;
+
+    // Children are grouped by rule, not in the order they are written
+    let mut arguments = arguments.get_children();
+    arguments.sort_by_key(|argument| argument.get().range.start);
+    let arguments: Vec<Argument> = arguments
+        .into_iter()
+        .filter(|argument| argument.get().kind == NodeKind::Node(config.argument.clone()))
+        .map(|argument| {
+            let name = argument
+                .get_child_of_kind(NodeKind::Node(config.argument_name.clone()))
+                .map(|name| name.get().content.trim().to_string());
+            let expression = argument
+                .get_children()
+                .into_iter()
+                .find(|child| child.get().symbol == language_def::Symbol::Expression)
+                .unwrap_or(argument);
+            Argument {
+                name,
+                text: compact(&expression.get().content),
+                range: expression.get().range,
+            }
+        })
+        .collect();
+
+    Some(Call {
+        arguments,
+        ..resolve_callee(symbol_table, config, node)?
+    })
+}
+
+/// Resolves the callable of a node of a call or instantiation rule, without its
+/// arguments, which may not be written yet.
+pub fn resolve_callee<Q: SymbolTableQuery>(
+    symbol_table: &Q,
+    config: &Calls,
+    node: &VisitNode,
+) -> Option<Call> {
+    let (instantiation, (_, callee_rule, _)) = find_rule(config, node)?;
+    let callee = node.get_child_of_kind(NodeKind::Node(callee_rule.clone()))?;
+
+    let text = compact(&callee.get().content);
+    let range = callee.get().range;
+    let signatures = if instantiation {
+        resolve_constructors(symbol_table, config, &text, range.start)
+    } else {
+        let type_arguments = get_owner_type_arguments(symbol_table, &text, range.start);
+        resolve_callees(symbol_table, config, &text, range.start)
+            .into_iter()
+            .map(|callable| Signature {
+                parameters: get_parameters(symbol_table, config, &callable),
+                callable,
+                type_arguments: type_arguments.clone(),
+            })
+            .collect()
+    };
+
+    Some(Call {
+        callee: text,
+        range,
+        arguments: vec![],
+        signatures,
+    })
+}
+
+/// Whether a node is of a call or instantiation rule, with the children of the rule.
+fn find_rule<'a>(
+    config: &'a Calls,
+    node: &VisitNode,
+) -> Option<(bool, &'a (String, String, String))> {
+    let NodeKind::Node(rule) = &node.get().kind else {
+        return None;
+    };
+    let is_rule = |(r, _, _): &&(String, String, String)| r == rule;
+
+    match config.rules.iter().find(is_rule) {
+        Some(call) => Some((false, call)),
+        None => Some((true, config.instantiations.iter().find(is_rule)?)),
+    }
+}
+
+/// Callables a call can run, given the text naming them, e.g. `f`, `pkt.extract`
+/// or `c.apply`. Overloaded methods resolve to each of their declarations.
+pub fn resolve_callees<Q: SymbolTableQuery>(
+    symbol_table: &Q,
+    config: &Calls,
+    text: &str,
+    position: Position,
+) -> Vec<Symbol> {
+    // Type arguments don't change the callable, e.g. `f<bit<8>>`
+    let text: String = text
+        .split('<')
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect();
+    let mut names = text.split('.');
+
+    let Some(head) = names
+        .next()
+        .and_then(|name| symbol_table.get_symbol_at_pos(name.to_string(), position))
+    else {
+        return vec![];
+    };
+
+    let mut callees = vec![head.clone()];
+    for member in names {
+        let Some(owner) = callees.first() else {
+            return vec![];
+        };
+        let owner_type = owner
+            .get_type_symbol()
+            .and_then(|id| symbol_table.get_symbol(id));
+
+        if member == config.apply {
+            callees = vec![owner_type.unwrap_or(owner).clone()];
+            continue;
+        }
+
+        callees = owner_type
+            .and_then(|owner_type| owner_type.get_field_scope_id())
+            .map(|scope_id| symbol_table.get_symbols_in_scope(scope_id))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|symbol| symbol.get_name() == member)
+            .collect();
+    }
+
+    callees
+}
+
+/// Parameters of a callable, in the order they are declared.
+pub fn get_parameters<Q: SymbolTableQuery>(
+    symbol_table: &Q,
+    config: &Calls,
+    callable: &Symbol,
+) -> Vec<Symbol> {
+    let Some(scope_id) = callable.get_field_scope_id() else {
+        return vec![];
+    };
+
+    let mut parameters: Vec<Symbol> = symbol_table
+        .get_symbols_in_scope(scope_id)
+        .into_iter()
+        .filter(|symbol| config.parameters.contains(&symbol.get_kind()))
+        .collect();
+    parameters.sort_by_key(|parameter| parameter.get_definition_range().start);
+
+    parameters
+}
+
+/// Whether a parameter can be left without an argument.
+pub fn is_optional(config: &Calls, parameter: &Symbol) -> bool {
+    let declaration = parameter.get_declaration();
+    config
+        .optional
+        .iter()
+        .any(|text| declaration.contains(text.as_str()))
+}
+
+/// Constructors of an instantiated type, the members named after it.
+fn resolve_constructors<Q: SymbolTableQuery>(
+    symbol_table: &Q,
+    config: &Calls,
+    type_text: &str,
+    position: Position,
+) -> Vec<Signature> {
+    let name = type_text.split('<').next().unwrap_or_default();
+    let Some(type_symbol) = symbol_table.get_symbol_at_pos(name.to_string(), position) else {
+        return vec![];
+    };
+    let Some(scope_id) = type_symbol.get_field_scope_id() else {
+        return vec![];
+    };
+    let type_arguments = get_type_arguments(type_symbol, type_text);
+
+    symbol_table
+        .get_symbols_in_scope(scope_id)
+        .into_iter()
+        .filter(|symbol| symbol.get_name() == name)
+        .map(|callable| Signature {
+            parameters: get_parameters(symbol_table, config, &callable),
+            callable,
+            type_arguments: type_arguments.clone(),
+        })
+        .collect()
+}
+
+/// Type arguments of the instance a method is called on, e.g. `r` in `r.read`.
+fn get_owner_type_arguments<Q: SymbolTableQuery>(
+    symbol_table: &Q,
+    text: &str,
+    position: Position,
+) -> Vec<(String, String)> {
+    let Some((owner, _)) = text.rsplit_once('.') else {
+        return vec![];
+    };
+    let Some(owner) = symbol_table.get_symbol_at_pos(owner.to_string(), position) else {
+        return vec![];
+    };
+    let (Some(type_symbol), Some(type_text)) = (
+        owner
+            .get_type_symbol()
+            .and_then(|id| symbol_table.get_symbol(id)),
+        owner.get_type_name(),
+    ) else {
+        return vec![];
+    };
+
+    get_type_arguments(type_symbol, &compact(type_text))
+}
+
+/// Pairs the type parameters of a generic type's declaration with the types
+/// given to it, e.g. `extern register<T>` and `register<bit<32>>`.
+fn get_type_arguments(type_symbol: &Symbol, type_text: &str) -> Vec<(String, String)> {
+    let declaration = compact(type_symbol.get_declaration());
+    let name = type_symbol.get_name();
+    let Some(start) = declaration.find(&format!("{}<", name)) else {
+        return vec![];
+    };
+
+    let parameters = split_type_list(&declaration[start + name.len()..]);
+    let arguments = split_type_list(type_text.strip_prefix(name.as_str()).unwrap_or_default());
+    if parameters.len() != arguments.len() {
+        return vec![];
+    }
+
+    parameters.into_iter().zip(arguments).collect()
+}
+
+/// Types between the angle brackets starting a text, e.g. `bit<8>` and `T` in `<bit<8>, T>`.
+fn split_type_list(text: &str) -> Vec<String> {
+    let Some(inner) = text.strip_prefix('<') else {
+        return vec![];
+    };
+
+    let mut types = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' if depth == 0 => {
+                types.push(inner[start..i].to_string());
+                return types;
+            }
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(inner[start..i].to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    // The list isn't closed
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_type_list;
+
+    #[test]
+    fn test_split_type_list() {
+        assert_eq!(split_type_list("<T> {"), vec!["T"]);
+        assert_eq!(split_type_list("<bit<8>,T>"), vec!["bit<8>", "T"]);
+        assert!(split_type_list("<bit<8>").is_empty());
+        assert!(split_type_list("register").is_empty());
+    }
+}
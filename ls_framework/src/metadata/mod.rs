@@ -1,9 +1,16 @@
 mod ast;
 mod ast_manager;
+mod calls;
 mod st_manager;
 mod symbol_table;
+mod types;
 
-pub use ast::{Ast, Node, NodeKind, VisitNode, Visitable};
+pub use ast::{
+    Ast, Direction, Literal, Node, NodeKind, Operator, SourceEdit, TypeDecType, VisitNode,
+    Visitable,
+};
 pub use ast_manager::{AstEditor, AstManager, AstQuery};
+pub use calls::{get_parameters, is_optional, resolve_call, resolve_callee, Call, Signature};
 pub use st_manager::{SymbolTableEditor, SymbolTableManager, SymbolTableQuery};
-pub use symbol_table::{Symbol, SymbolTable, SymbolTableActions};
+pub use symbol_table::{Imports, ScopeId, Symbol, SymbolTable, SymbolTableActions};
+pub use types::{parse_integer, Type, TypeChecker};
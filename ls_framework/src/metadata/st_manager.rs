@@ -1,35 +1,51 @@
 use core::fmt;
 use std::fmt::Debug;
+use std::sync::Arc;
 
-use super::symbol_table::{ScopeId, SymbolId, SymbolTable};
+use super::symbol_table::{Imports, ScopeId, SymbolId, SymbolTable, UndefinedSymbol};
 use super::{Ast, Symbol};
 
-use tower_lsp::lsp_types::Position;
+use tower_lsp::lsp_types::{Location, Position, Range, Url};
 
 use crate::metadata::symbol_table::SymbolTableActions;
 
 pub trait SymbolTableEditor {
-    fn update(&mut self, ast: &mut Ast);
+    fn update(&mut self, uri: Url, ast: &mut Ast, imports: &Imports);
 }
 
 pub trait SymbolTableQuery {
     fn get_symbols_at_pos(&self, position: Position) -> Vec<Symbol>;
     fn get_symbols_in_scope(&self, scope_id: ScopeId) -> Vec<Symbol>;
+    fn get_root_scope_id(&self) -> Option<ScopeId>;
+    fn get_child_scopes(&self, scope_id: ScopeId) -> Vec<ScopeId>;
+    fn get_scope_range(&self, scope_id: ScopeId) -> Option<Range>;
     fn get_symbol_at_pos(&self, name: String, position: Position) -> Option<&Symbol>;
     fn get_all_symbols(&self) -> Vec<Symbol>;
+    fn get_imported_symbols(&self) -> Vec<Symbol>;
     fn get_symbol(&self, symbol_id: SymbolId) -> Option<&Symbol>;
+    fn get_symbols_defined_at(&self, location: &Location) -> Vec<Symbol>;
+    fn get_undefined_symbols(&self) -> Vec<UndefinedSymbol>;
+    fn has_unresolved_imports(&self) -> bool;
+    fn get_import_uris(&self) -> Vec<Url>;
+    fn get_duplicate_symbols(&self) -> Vec<(Symbol, Symbol)>;
+    fn get_shadowing_symbols(&self) -> Vec<(Symbol, Symbol)>;
 }
 
 #[derive(Debug, Clone)]
 pub struct SymbolTableManager {
-    symbol_table: SymbolTable,
+    symbol_table: Arc<SymbolTable>,
 }
 
 impl SymbolTableManager {
-    pub fn new(ast: &mut Ast) -> SymbolTableManager {
-        let symbol_table = SymbolTable::new(ast);
+    pub fn new(uri: Url, ast: &mut Ast, imports: &Imports) -> SymbolTableManager {
+        let symbol_table = Arc::new(SymbolTable::new(uri, ast, imports));
         SymbolTableManager { symbol_table }
     }
+
+    /// The table, shared with the files importing it.
+    pub fn get_symbol_table(&self) -> Arc<SymbolTable> {
+        self.symbol_table.clone()
+    }
 }
 
 impl fmt::Display for SymbolTableManager {
@@ -51,6 +67,10 @@ impl SymbolTableQuery for SymbolTableManager {
         self.symbol_table.get_all_symbols()
     }
 
+    fn get_imported_symbols(&self) -> Vec<Symbol> {
+        self.symbol_table.get_imported_symbols()
+    }
+
     fn get_symbol(&self, symbol_id: SymbolId) -> Option<&Symbol> {
         self.symbol_table.get_symbol(symbol_id)
     }
@@ -58,10 +78,46 @@ impl SymbolTableQuery for SymbolTableManager {
     fn get_symbols_in_scope(&self, scope_id: ScopeId) -> Vec<Symbol> {
         self.symbol_table.get_symbols_in_scope(scope_id)
     }
+
+    fn get_root_scope_id(&self) -> Option<ScopeId> {
+        self.symbol_table.get_root_scope_id()
+    }
+
+    fn get_child_scopes(&self, scope_id: ScopeId) -> Vec<ScopeId> {
+        self.symbol_table.get_child_scopes(scope_id)
+    }
+
+    fn get_scope_range(&self, scope_id: ScopeId) -> Option<Range> {
+        self.symbol_table.get_scope_range(scope_id)
+    }
+
+    fn get_symbols_defined_at(&self, location: &Location) -> Vec<Symbol> {
+        self.symbol_table.get_symbols_defined_at(location)
+    }
+
+    fn get_undefined_symbols(&self) -> Vec<UndefinedSymbol> {
+        self.symbol_table.get_undefined_symbols()
+    }
+
+    fn has_unresolved_imports(&self) -> bool {
+        self.symbol_table.has_unresolved_imports()
+    }
+
+    fn get_import_uris(&self) -> Vec<Url> {
+        self.symbol_table.get_import_uris()
+    }
+
+    fn get_duplicate_symbols(&self) -> Vec<(Symbol, Symbol)> {
+        self.symbol_table.get_duplicate_symbols()
+    }
+
+    fn get_shadowing_symbols(&self) -> Vec<(Symbol, Symbol)> {
+        self.symbol_table.get_shadowing_symbols()
+    }
 }
 
 impl SymbolTableEditor for SymbolTableManager {
-    fn update(&mut self, ast: &mut Ast) {
-        *self = SymbolTableManager::new(ast)
+    fn update(&mut self, uri: Url, ast: &mut Ast, imports: &Imports) {
+        Arc::make_mut(&mut self.symbol_table).update(uri, ast, imports)
     }
 }
@@ -1,19 +1,55 @@
-use crate::{language_def, metadata::NodeKind};
+use crate::{
+    language_def::{self, LanguageDefinition, ModifierCondition},
+    line_index::LineIndex,
+    lsp_mappings::HighlightModifier,
+    metadata::NodeKind,
+};
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Weak};
 
-use crate::metadata::ast::{Ast, Visitable};
+use crate::metadata::ast::{Ast, Direction, TypeDecType, Visitable};
 use indextree::{Arena, NodeId};
-use tower_lsp::lsp_types::{Position, Range};
+use tower_lsp::lsp_types::{Location, Position, Range, Url};
 
 use super::Node;
 
 pub type ScopeId = NodeId;
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct SymbolTable {
+    uri: Url,
     arena: Arena<ScopeSymbolTable>,
     root_id: Option<ScopeId>,
-    undefined_list: Vec<Range>,
+    imports: Vec<(Url, ScopeId)>,
+    unresolved_imports: bool,
+    undefined_list: Vec<UndefinedSymbol>,
+    /// AST the scopes were parsed from, they can be patched while it is edited in place.
+    ast_id: usize,
+    /// Tables the imported scopes were copied from, which are kept while they don't change.
+    import_sources: Vec<(Url, Weak<SymbolTable>)>,
+}
+
+/// Symbol tables of the files included by a file.
+#[derive(Debug, Default, Clone)]
+pub struct Imports {
+    /// Shared with the files they belong to, they are only copied into the importing table.
+    pub tables: Vec<(Url, Arc<SymbolTable>)>,
+    /// Set when some of the includes couldn't be resolved, in which case the
+    /// imported symbols are incomplete.
+    pub unresolved: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UndefinedSymbol {
+    pub name: String,
+    pub range: Range,
+    /// Name of the innermost named scope the lookup started from, `None` for
+    /// the global scope.
+    pub scope_name: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -35,8 +71,18 @@ pub trait SymbolTableActions {
     fn get_symbol(&self, id: SymbolId) -> Option<&Symbol>;
     fn get_symbol_mut(&mut self, id: SymbolId) -> Option<&mut Symbol>;
     fn get_all_symbols(&self) -> Vec<Symbol>;
+    fn get_imported_symbols(&self) -> Vec<Symbol>;
     fn get_symbols_in_scope_at_pos(&self, position: Position) -> Vec<Symbol>;
     fn get_symbols_in_scope(&self, scope_id: ScopeId) -> Vec<Symbol>;
+    fn get_root_scope_id(&self) -> Option<ScopeId>;
+    fn get_child_scopes(&self, scope_id: ScopeId) -> Vec<ScopeId>;
+    fn get_scope_range(&self, scope_id: ScopeId) -> Option<Range>;
+    fn get_symbols_defined_at(&self, location: &Location) -> Vec<Symbol>;
+    fn get_undefined_symbols(&self) -> Vec<UndefinedSymbol>;
+    fn get_import_uris(&self) -> Vec<Url>;
+    fn get_duplicate_symbols(&self) -> Vec<(Symbol, Symbol)>;
+    fn get_shadowing_symbols(&self) -> Vec<(Symbol, Symbol)>;
+    fn has_unresolved_imports(&self) -> bool;
     fn get_top_level_symbols(&self) -> Vec<Symbol>;
     fn get_symbol_at_pos(&self, name: String, position: Position) -> Option<&Symbol>;
     fn rename_symbol(&mut self, id: usize, new_name: String);
@@ -82,6 +128,18 @@ impl SymbolTableActions for SymbolTable {
             }
         }
 
+        for (_, import_root_id) in &self.imports {
+            symbols.append(
+                &mut self
+                    .arena
+                    .get(*import_root_id)
+                    .unwrap()
+                    .get()
+                    .symbols
+                    .clone(),
+            );
+        }
+
         symbols
     }
 
@@ -95,7 +153,7 @@ impl SymbolTableActions for SymbolTable {
     }
 
     fn rename_symbol(&mut self, id: usize, new_name: String) {
-        for scope in self.arena.iter_mut() {
+        for scope in self.arena.iter_mut().filter(|scope| !scope.is_removed()) {
             if let Some(symbol) = scope.get_mut().symbols.get_mut(id) {
                 symbol.name = new_name;
                 break;
@@ -114,7 +172,8 @@ impl SymbolTableActions for SymbolTable {
             }
         }
 
-        None
+        let symbol_id = self.find_imported_symbol(&name, None)?;
+        self.get_symbol(symbol_id)
     }
 
     fn get_all_symbols(&self) -> Vec<Symbol> {
@@ -127,16 +186,137 @@ impl SymbolTableActions for SymbolTable {
         symbols
     }
 
+    fn get_imported_symbols(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = Vec::new();
+
+        for (_, import_root_id) in &self.imports {
+            for scope_id in import_root_id.descendants(&self.arena) {
+                symbols.append(&mut self.arena.get(scope_id).unwrap().get().symbols.clone());
+            }
+        }
+
+        symbols
+    }
+
     fn get_symbols_in_scope(&self, scope_id: ScopeId) -> Vec<Symbol> {
         self.arena.get(scope_id).unwrap().get().symbols.clone()
     }
+
+    fn get_root_scope_id(&self) -> Option<ScopeId> {
+        self.root_id
+    }
+
+    fn get_child_scopes(&self, scope_id: ScopeId) -> Vec<ScopeId> {
+        scope_id.children(&self.arena).collect()
+    }
+
+    fn get_scope_range(&self, scope_id: ScopeId) -> Option<Range> {
+        Some(self.arena.get(scope_id)?.get().range)
+    }
+
+    fn get_symbols_defined_at(&self, location: &Location) -> Vec<Symbol> {
+        // Imported scopes can be copied more than once (e.g. through transitive
+        // includes), so every copy of the symbol is returned.
+        self.arena
+            .iter()
+            .filter(|scope| !scope.is_removed())
+            .flat_map(|scope| scope.get().symbols.iter())
+            .filter(|symbol| symbol.uri == location.uri && symbol.def_position == location.range)
+            .cloned()
+            .collect()
+    }
+
+    fn get_undefined_symbols(&self) -> Vec<UndefinedSymbol> {
+        self.undefined_list.clone()
+    }
+
+    fn has_unresolved_imports(&self) -> bool {
+        self.unresolved_imports
+    }
+
+    /// Returns the files included by the file, directly or through another include.
+    fn get_import_uris(&self) -> Vec<Url> {
+        self.imports.iter().map(|(uri, _)| uri.clone()).collect()
+    }
+
+    /// Returns the pairs of symbols declared more than once in the same scope,
+    /// the first declaration coming first. Callables with different numbers of
+    /// parameters are overloads rather than duplicates.
+    fn get_duplicate_symbols(&self) -> Vec<(Symbol, Symbol)> {
+        let Some(root_id) = self.root_id else {
+            return vec![];
+        };
+
+        let mut duplicates = vec![];
+        for scope_id in root_id.descendants(&self.arena) {
+            let mut symbols = self.arena.get(scope_id).unwrap().get().symbols.clone();
+            symbols.sort_by_key(|s| s.def_position.start);
+
+            for (i, symbol) in symbols.iter().enumerate() {
+                let arity = self.count_parameters(symbol);
+                if let Some(first) = symbols[..i]
+                    .iter()
+                    .find(|s| s.name == symbol.name && self.count_parameters(s) == arity)
+                {
+                    duplicates.push((first.clone(), symbol.clone()));
+                }
+            }
+        }
+
+        duplicates
+    }
+
+    /// Returns the pairs of symbols hiding a symbol of an enclosing scope, the
+    /// hiding symbol coming first.
+    fn get_shadowing_symbols(&self) -> Vec<(Symbol, Symbol)> {
+        let Some(root_id) = self.root_id else {
+            return vec![];
+        };
+
+        let mut shadowing = vec![];
+        for scope_id in root_id.descendants(&self.arena).skip(1) {
+            for symbol in self.arena.get(scope_id).unwrap().get().symbols.iter() {
+                let shadowed = scope_id.ancestors(&self.arena).skip(1).find_map(|id| {
+                    self.arena
+                        .get(id)?
+                        .get()
+                        .symbols
+                        .iter()
+                        .find(|s| s.name == symbol.name)
+                });
+
+                if let Some(shadowed) = shadowed {
+                    shadowing.push((symbol.clone(), shadowed.clone()));
+                }
+            }
+        }
+
+        shadowing
+    }
 }
 
 impl SymbolTable {
-    pub fn new(ast: &mut Ast) -> SymbolTable {
-        let mut table = SymbolTable::default();
+    pub fn new(uri: Url, ast: &mut Ast, imports: &Imports) -> SymbolTable {
+        let mut table = SymbolTable {
+            uri,
+            arena: Arena::new(),
+            root_id: None,
+            imports: vec![],
+            unresolved_imports: imports.unresolved,
+            undefined_list: vec![],
+            ast_id: ast.get_id(),
+            import_sources: vec![],
+        };
+
+        for node in ast.get_arena().iter_mut().filter(|node| !node.is_removed()) {
+            node.get_mut().linked_symbol = None;
+        }
 
         table.root_id = Some(table.parse_scope(ast.visit_root().get_id(), ast.get_arena()));
+        for (uri, imported_table) in &imports.tables {
+            table.import(uri.clone(), imported_table);
+        }
+        table.import_sources = get_sources(imports);
         table.parse_usages(ast.get_arena());
         table.parse_types(ast.visit_root().get_id(), ast.get_arena());
         table.parse_member_usages(ast.visit_root().get_id(), ast.get_arena());
@@ -144,6 +324,247 @@ impl SymbolTable {
         table
     }
 
+    /// Updates the table after its AST was edited in place. The scopes of the
+    /// top-level declarations that weren't translated again are kept with their
+    /// positions updated, the others are parsed again, and the usages are
+    /// resolved again since any declaration can change what a name refers to.
+    /// The table is built again when the AST or the imported tables were replaced.
+    pub fn update(&mut self, uri: Url, ast: &mut Ast, imports: &Imports) {
+        let Some(root_id) = self.root_id else {
+            *self = SymbolTable::new(uri, ast, imports);
+            return;
+        };
+        let same_imports = self.import_sources.len() == imports.tables.len()
+            && self.import_sources.iter().zip(&imports.tables).all(
+                |((source_uri, source), (uri, table))| {
+                    source_uri == uri && Weak::ptr_eq(source, &Arc::downgrade(table))
+                },
+            );
+        if uri != self.uri || ast.get_id() != self.ast_id || !same_imports {
+            *self = SymbolTable::new(uri, ast, imports);
+            return;
+        }
+
+        let root_node_id = ast.visit_root().get_id();
+        let ast_arena = ast.get_arena();
+
+        // Usages are linked again, the links of the declarations kept stay valid
+        for node in ast_arena.iter_mut().filter(|node| !node.is_removed()) {
+            let node = node.get_mut();
+            if matches!(
+                node.symbol,
+                language_def::Symbol::Usage | language_def::Symbol::MemberUsage
+            ) {
+                node.linked_symbol = None;
+            }
+        }
+
+        // Top-level scopes whose node wasn't translated again
+        let mut kept: HashMap<NodeId, ScopeId> = HashMap::new();
+        let top_level: Vec<ScopeId> = root_id.children(&self.arena).collect();
+        for scope_id in top_level {
+            match self.arena[scope_id]
+                .get()
+                .node
+                .filter(|node_id| ast_arena.get(*node_id).is_some())
+            {
+                Some(node_id) => {
+                    scope_id.detach(&mut self.arena);
+                    kept.insert(node_id, scope_id);
+                }
+                None => scope_id.remove_subtree(&mut self.arena),
+            }
+        }
+
+        let root_node = ast_arena[root_node_id].get();
+        let mut root_scope = ScopeSymbolTable::new(root_node.range, root_node_id);
+        root_scope.symbols = self.get_builtin_symbols(root_node);
+        let kept_symbols = std::mem::replace(self.arena[root_id].get_mut(), root_scope).symbols;
+        self.parse_declarations(root_id, root_node_id, ast_arena, &kept, &kept_symbols);
+
+        // Usages and types are found again
+        for scope in self.arena.iter_mut().filter(|scope| !scope.is_removed()) {
+            for symbol in scope.get_mut().symbols.iter_mut() {
+                symbol.usages.clear();
+            }
+        }
+        for scope_id in root_id.descendants(&self.arena).collect::<Vec<ScopeId>>() {
+            for symbol in self.arena[scope_id].get_mut().symbols.iter_mut() {
+                symbol.type_symbol = None;
+            }
+        }
+        self.unresolved_imports = imports.unresolved
+            || imports
+                .tables
+                .iter()
+                .any(|(_, table)| table.unresolved_imports);
+        self.undefined_list.clear();
+
+        self.parse_usages(ast_arena);
+        self.parse_types(root_node_id, ast_arena);
+        self.parse_member_usages(root_node_id, ast_arena);
+    }
+
+    /// Updates the positions of a kept scope and of the symbols declared in it
+    /// from the nodes they were parsed from, which moved with the edit.
+    fn refresh_scope(&mut self, scope_id: ScopeId, node_id: NodeId, ast_arena: &Arena<Node>) {
+        let scope_ids: Vec<ScopeId> = scope_id.descendants(&self.arena).collect();
+        for id in scope_ids {
+            let scope = self.arena[id].get_mut();
+            let Some(node) = scope.node.and_then(|node_id| ast_arena.get(node_id)) else {
+                continue;
+            };
+            scope.range = node.get().range;
+
+            let start = Range::new(scope.range.start, scope.range.start);
+            for symbol in scope.symbols.iter_mut().filter(|symbol| symbol.builtin) {
+                symbol.def_position = start;
+            }
+        }
+
+        // Only the names of declarations are still linked
+        for id in node_id.descendants(ast_arena) {
+            let node = ast_arena[id].get();
+            if let Some(symbol) = node
+                .linked_symbol
+                .clone()
+                .and_then(|symbol_id| self.get_symbol_mut(symbol_id))
+            {
+                symbol.def_position = node.range;
+            }
+        }
+    }
+
+    /// Fingerprint of what the files including this one can see: the symbols of
+    /// its scopes and of its imports, without their usages. Editing the file
+    /// without changing it leaves the tables of the including files valid.
+    pub fn get_exports_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let roots = self
+            .root_id
+            .iter()
+            .chain(self.imports.iter().map(|(_, id)| id));
+
+        for root_id in roots {
+            for scope_id in root_id.descendants(&self.arena) {
+                for symbol in &self.arena.get(scope_id).unwrap().get().symbols {
+                    symbol.hash_declaration(&mut hasher);
+                }
+                // Marks the end of the scope, so moving a symbol to another scope counts
+                hasher.write_u8(0);
+            }
+        }
+        self.unresolved_imports.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// Copies the scopes of an included file's table into this table's arena, making
+    /// its top-level symbols (and those of its own includes) visible to lookups.
+    fn import(&mut self, uri: Url, table: &SymbolTable) {
+        self.unresolved_imports |= table.unresolved_imports;
+        let Some(imported_root_id) = table.root_id else {
+            return;
+        };
+
+        let mut id_map: HashMap<ScopeId, ScopeId> = HashMap::new();
+        let roots = std::iter::once((uri, imported_root_id)).chain(table.imports.iter().cloned());
+
+        for (root_uri, root_id) in roots {
+            for scope_id in root_id.descendants(&table.arena) {
+                let scope_node = table.arena.get(scope_id).unwrap();
+
+                let mut scope = scope_node.get().clone();
+                for symbol in scope.symbols.iter_mut() {
+                    symbol.usages.clear();
+                }
+
+                let new_scope_id = self.arena.new_node(scope);
+                if let Some(parent_id) = scope_node.parent() {
+                    id_map[&parent_id].append(new_scope_id, &mut self.arena);
+                }
+                id_map.insert(scope_id, new_scope_id);
+            }
+
+            if !self.imports.iter().any(|(u, _)| *u == root_uri) {
+                self.imports.push((root_uri, id_map[&root_id]));
+            }
+        }
+
+        for new_scope_id in id_map.values() {
+            for symbol in self
+                .arena
+                .get_mut(*new_scope_id)
+                .unwrap()
+                .get_mut()
+                .symbols
+                .iter_mut()
+            {
+                symbol.field_scope_id = symbol
+                    .field_scope_id
+                    .and_then(|id| id_map.get(&id).copied());
+                symbol.type_symbol = symbol.type_symbol.as_ref().and_then(|id| {
+                    Some(SymbolId::new(*id_map.get(&id.symbol_table_id)?, id.index))
+                });
+            }
+        }
+    }
+
+    /// Number of parameters of a callable, `None` for other symbols.
+    fn count_parameters(&self, symbol: &Symbol) -> Option<usize> {
+        let parameters = &LanguageDefinition::get().calls.as_ref()?.parameters;
+        let scope = self.arena.get(symbol.field_scope_id?)?.get();
+
+        Some(
+            scope
+                .symbols
+                .iter()
+                .filter(|s| parameters.contains(&s.kind))
+                .count(),
+        )
+    }
+
+    fn find_imported_symbol(&self, name: &str, arguments: Option<usize>) -> Option<SymbolId> {
+        self.imports.iter().find_map(|(_, root_id)| {
+            let symbols = &self.arena.get(*root_id)?.get().symbols;
+            let index = self.find_overload(symbols, name, arguments)?;
+
+            Some(SymbolId::new(*root_id, index))
+        })
+    }
+
+    /// Index of the symbol named `name` among `symbols`. When the name is called
+    /// with `arguments` arguments, the overload taking as many parameters is preferred.
+    fn find_overload(
+        &self,
+        symbols: &[Symbol],
+        name: &str,
+        arguments: Option<usize>,
+    ) -> Option<usize> {
+        arguments
+            .and_then(|count| {
+                symbols
+                    .iter()
+                    .position(|s| s.name == name && self.count_parameters(s) == Some(count))
+            })
+            .or_else(|| symbols.iter().position(|s| s.name == name))
+    }
+
+    /// Returns the name of the symbol owning the innermost named scope containing
+    /// `scope_id`, or `None` when it is the global scope.
+    fn get_scope_name(&self, scope_id: ScopeId) -> Option<String> {
+        scope_id.predecessors(&self.arena).find_map(|id| {
+            let parent_id = self.arena.get(id)?.parent()?;
+            self.arena
+                .get(parent_id)?
+                .get()
+                .symbols
+                .iter()
+                .find(|s| s.field_scope_id == Some(id))
+                .map(|s| s.name.clone())
+        })
+    }
+
     fn get_scope_id(&self, position: Position) -> Option<ScopeId> {
         self._get_scope_id(position, self.root_id?)
     }
@@ -168,51 +589,112 @@ impl SymbolTable {
     }
 
     fn parse_scope(&mut self, node_id: NodeId, ast_arena: &mut Arena<Node>) -> ScopeId {
-        let table = ScopeSymbolTable::new(ast_arena.get(node_id).unwrap().get().range);
-        let current_table_node_id = self.arena.new_node(table);
+        let node = ast_arena.get(node_id).unwrap().get();
+        let mut table = ScopeSymbolTable::new(node.range, node_id);
+        table.symbols = self.get_builtin_symbols(node);
+        let scope_id = self.arena.new_node(table);
 
-        let mut queue: Vec<NodeId> = node_id.children(ast_arena).collect();
+        self.parse_declarations(scope_id, node_id, ast_arena, &HashMap::new(), &[]);
+
+        scope_id
+    }
+
+    /// Adds to a scope the symbols declared under `node_id` and the scopes they open.
+    /// The scopes of `kept` are reused for the nodes they were parsed from, and so
+    /// are the symbols of `kept_symbols` whose names are still linked to them.
+    fn parse_declarations(
+        &mut self,
+        current_table_node_id: ScopeId,
+        node_id: NodeId,
+        ast_arena: &mut Arena<Node>,
+        kept: &HashMap<NodeId, ScopeId>,
+        kept_symbols: &[Symbol],
+    ) {
+        let mut queue = get_children_in_reverse(node_id, ast_arena);
 
         while let Some(node_id) = queue.pop() {
             let symbol_index = if let crate::language_def::Symbol::Init {
                 kind,
                 name_node,
-                type_node: _,
+                type_node,
             } = &ast_arena.get(node_id).unwrap().get().symbol
             {
-                let name_node_id = node_id
-                    .children(ast_arena)
-                    .find(|id| {
-                        ast_arena.get(*id).unwrap().get().kind == NodeKind::Node(name_node.clone())
-                    })
-                    .unwrap();
+                let name_node_id = node_id.children(ast_arena).find(|id| {
+                    ast_arena.get(*id).unwrap().get().kind == NodeKind::Node(name_node.clone())
+                });
 
-                let name_node = ast_arena.get(name_node_id).unwrap().get();
-
-                let symbol = Symbol::new(name_node.content.clone(), kind.clone(), name_node.range);
+                if let Some(name_node_id) = name_node_id {
+                    let name_node = ast_arena.get(name_node_id).unwrap().get();
+                    let kept_symbol = name_node
+                        .linked_symbol
+                        .as_ref()
+                        .filter(|id| id.symbol_table_id == current_table_node_id)
+                        .and_then(|id| kept_symbols.get(id.index));
+
+                    let symbol = match kept_symbol {
+                        Some(kept_symbol) => Symbol {
+                            def_position: name_node.range,
+                            ..kept_symbol.clone()
+                        },
+                        None => {
+                            let mut symbol = Symbol::new(
+                                name_node.content.clone(),
+                                kind.clone(),
+                                self.uri.clone(),
+                                name_node.range,
+                            );
+                            symbol.declaration_modifiers =
+                                get_declaration_modifiers(kind, node_id, ast_arena);
+                            symbol.declaration = get_declaration_snippet(node_id, ast_arena);
+                            symbol.direction = get_direction(node_id, ast_arena);
+                            symbol.type_name = type_node.as_ref().and_then(|type_node| {
+                                let type_node_id = node_id.children(ast_arena).find(|id| {
+                                    ast_arena.get(*id).unwrap().get().kind
+                                        == NodeKind::Node(type_node.clone())
+                                })?;
+                                Some(ast_arena.get(type_node_id).unwrap().get().content.clone())
+                            });
+                            if let NodeKind::Node(node_name) =
+                                &ast_arena.get(node_id).unwrap().get().kind
+                            {
+                                symbol.type_declaration =
+                                    LanguageDefinition::get().get_type_declaration(node_name);
+                            }
+                            symbol
+                        }
+                    };
 
-                let symbols = &mut self
-                    .arena
-                    .get_mut(current_table_node_id)
-                    .unwrap()
-                    .get_mut()
-                    .symbols;
-                symbols.push(symbol);
+                    let symbols = &mut self
+                        .arena
+                        .get_mut(current_table_node_id)
+                        .unwrap()
+                        .get_mut()
+                        .symbols;
+                    symbols.push(symbol);
 
-                let index = symbols.len() - 1;
-                ast_arena
-                    .get_mut(name_node_id)
-                    .unwrap()
-                    .get_mut()
-                    .link(current_table_node_id, index);
+                    let index = symbols.len() - 1;
+                    ast_arena
+                        .get_mut(name_node_id)
+                        .unwrap()
+                        .get_mut()
+                        .link(current_table_node_id, index);
 
-                Some(index)
+                    Some(index)
+                } else {
+                    None
+                }
             } else {
                 None
             };
 
             if ast_arena.get(node_id).unwrap().get().kind.is_scope_node() {
-                let subtable = self.parse_scope(node_id, ast_arena);
+                let subtable = match kept.get(&node_id) {
+                    Some(scope_id) => {
+                        self.refresh_scope(*scope_id, node_id, ast_arena);
+                        *scope_id
+                    }
+                    None => self.parse_scope(node_id, ast_arena),
+                };
 
                 if let Some(i) = symbol_index {
                     self.arena
@@ -225,35 +707,52 @@ impl SymbolTable {
 
                 current_table_node_id.append(subtable, &mut self.arena);
             } else {
-                queue.append(&mut node_id.children(ast_arena).collect());
+                queue.append(&mut get_children_in_reverse(node_id, ast_arena));
             }
         }
+    }
 
-        current_table_node_id
+    /// Symbols the language declares in the scope opened by `node`, placed at its start.
+    fn get_builtin_symbols(&self, node: &Node) -> Vec<Symbol> {
+        let NodeKind::Node(node_name) = &node.kind else {
+            return vec![];
+        };
+        let Some(rule) = LanguageDefinition::get().rule_with_name(node_name) else {
+            return vec![];
+        };
+
+        let start = Range::new(node.range.start, node.range.start);
+        rule.builtin_symbols
+            .iter()
+            .map(|(name, kind)| {
+                let mut symbol = Symbol::new(name.clone(), kind.clone(), self.uri.clone(), start);
+                symbol.builtin = true;
+                symbol
+            })
+            .collect()
     }
 
     fn parse_usages(&mut self, arena: &mut Arena<Node>) {
-        for node in arena
-            .iter_mut()
-            .filter(|node| matches!(node.get().symbol, language_def::Symbol::Usage))
-        {
-            let node = node.get_mut();
-            let symbol_name = &node.content;
+        let ids: Vec<NodeId> = arena
+            .iter()
+            .filter(|node| {
+                !node.is_removed() && matches!(node.get().symbol, language_def::Symbol::Usage)
+            })
+            .filter_map(|node| arena.get_node_id(node))
+            .collect();
+
+        for node_id in ids {
+            let arguments = get_argument_count(node_id, arena);
+            let node = arena[node_id].get_mut();
+            let symbol_name = node.content.clone();
 
             let scope_id = self.get_scope_id(node.range.start).unwrap();
             let scope_ids: Vec<NodeId> = scope_id.predecessors(&self.arena).collect();
 
             let mut found = false;
             for id in scope_ids {
-                if let Some(index) = self
-                    .arena
-                    .get(id)
-                    .unwrap()
-                    .get()
-                    .symbols
-                    .iter()
-                    .position(|s| &s.name == symbol_name)
-                {
+                let symbols = &self.arena.get(id).unwrap().get().symbols;
+                if let Some(index) = self.find_overload(symbols, &symbol_name, arguments) {
                     let symbol = &mut self.arena.get_mut(id).unwrap().get_mut().symbols[index];
                     node.link(id, index);
                     found = true;
@@ -263,7 +762,21 @@ impl SymbolTable {
             }
 
             if !found {
-                self.undefined_list.push(node.range);
+                if let Some(symbol_id) = self.find_imported_symbol(&symbol_name, arguments) {
+                    node.link(symbol_id.symbol_table_id, symbol_id.index);
+                    self.get_symbol_mut(symbol_id)
+                        .unwrap()
+                        .add_usage(node.range);
+                    found = true;
+                }
+            }
+
+            if !found {
+                self.undefined_list.push(UndefinedSymbol {
+                    name: symbol_name,
+                    range: node.range,
+                    scope_name: self.get_scope_name(scope_id),
+                });
             }
         }
     }
@@ -276,13 +789,12 @@ impl SymbolTable {
                 type_node: Some(type_node_query),
             } = ast_arena.get(node_id).unwrap().get().symbol.clone()
             {
-                let type_node_id = node_id
-                    .children(ast_arena)
-                    .find(|id| {
-                        ast_arena.get(*id).unwrap().get().kind
-                            == NodeKind::Node(type_node_query.clone())
-                    })
-                    .unwrap();
+                let Some(type_node_id) = node_id.children(ast_arena).find(|id| {
+                    ast_arena.get(*id).unwrap().get().kind
+                        == NodeKind::Node(type_node_query.clone())
+                }) else {
+                    continue;
+                };
 
                 if let Some(symbol_id) = ast_arena
                     .get(type_node_id)
@@ -291,13 +803,11 @@ impl SymbolTable {
                     .linked_symbol
                     .clone()
                 {
-                    let name_node_id = node_id
-                        .children(ast_arena)
-                        .find(|id| {
-                            ast_arena.get(*id).unwrap().get().kind
-                                == NodeKind::Node(name_node.clone())
-                        })
-                        .unwrap();
+                    let Some(name_node_id) = node_id.children(ast_arena).find(|id| {
+                        ast_arena.get(*id).unwrap().get().kind == NodeKind::Node(name_node.clone())
+                    }) else {
+                        continue;
+                    };
 
                     if let Some(name_symbol_id) = ast_arena
                         .get(name_node_id)
@@ -339,14 +849,15 @@ impl SymbolTable {
                                     self.get_symbol(parent_type_symbol_id).unwrap();
 
                                 if let Some(field_scope_id) = parent_type_symbol.field_scope_id {
+                                    let member_symbol_index = self.find_overload(
+                                        &self.arena[field_scope_id].get().symbols,
+                                        &arena[id].get().content,
+                                        get_argument_count(id, arena),
+                                    );
                                     let scope_table =
                                         self.arena.get_mut(field_scope_id).unwrap().get_mut();
 
-                                    if let Some(member_symbol_index) =
-                                        scope_table.symbols.iter().position(|s| {
-                                            s.name == arena.get(id).unwrap().get().content
-                                        })
-                                    {
+                                    if let Some(member_symbol_index) = member_symbol_index {
                                         arena
                                             .get_mut(id)
                                             .unwrap()
@@ -386,17 +897,18 @@ impl SymbolTable {
                                         if let Some(field_scope_id) =
                                             parent_type_symbol.field_scope_id
                                         {
+                                            let member_symbol_index = self.find_overload(
+                                                &self.arena[field_scope_id].get().symbols,
+                                                &arena[id].get().content,
+                                                get_argument_count(id, arena),
+                                            );
                                             let scope_table = self
                                                 .arena
                                                 .get_mut(field_scope_id)
                                                 .unwrap()
                                                 .get_mut();
 
-                                            if let Some(member_symbol_index) =
-                                                scope_table.symbols.iter().position(|s| {
-                                                    s.name == arena.get(id).unwrap().get().content
-                                                })
-                                            {
+                                            if let Some(member_symbol_index) = member_symbol_index {
                                                 arena
                                                     .get_mut(id)
                                                     .unwrap()
@@ -425,7 +937,14 @@ impl fmt::Display for SymbolTable {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let mut output = String::new();
 
-        let mut sorted = self.arena.iter().collect::<Vec<_>>();
+        let Some(root_id) = self.root_id else {
+            return Ok(());
+        };
+
+        let mut sorted = root_id
+            .descendants(&self.arena)
+            .map(|id| self.arena.get(id).unwrap())
+            .collect::<Vec<_>>();
         sorted.sort_by(|a, b| a.get().range.start.cmp(&b.get().range.start));
 
         for node in sorted {
@@ -440,12 +959,15 @@ impl fmt::Display for SymbolTable {
 struct ScopeSymbolTable {
     range: Range,
     symbols: Vec<Symbol>,
+    /// AST node the scope was parsed from, in the AST of the file declaring it.
+    node: Option<NodeId>,
 }
 
 impl ScopeSymbolTable {
-    fn new(range: Range) -> ScopeSymbolTable {
+    fn new(range: Range, node: NodeId) -> ScopeSymbolTable {
         ScopeSymbolTable {
             range,
+            node: Some(node),
             ..Default::default()
         }
     }
@@ -474,28 +996,215 @@ impl fmt::Display for ScopeSymbolTable {
     }
 }
 
+/// Children of a node, the first one in the source coming last. Edited ASTs don't
+/// keep the children in the order of the source, which matters when some of them
+/// overlap, e.g. scopes of code with syntax errors.
+fn get_children_in_reverse(node_id: NodeId, ast_arena: &Arena<Node>) -> Vec<NodeId> {
+    let mut children: Vec<NodeId> = node_id.children(ast_arena).collect();
+    children.sort_by_key(|id| {
+        let range = ast_arena[*id].get().range;
+        Reverse((range.start, range.end))
+    });
+
+    children
+}
+
+/// Number of arguments passed to the name `node_id` when it is called, e.g. 2
+/// for `f` in `f(x, y)` or for `extract` in `pkt.extract(x, y)`, to tell its
+/// overloads apart.
+fn get_argument_count(node_id: NodeId, ast_arena: &Arena<Node>) -> Option<usize> {
+    let end = ast_arena[node_id].get().range.end;
+
+    // The innermost node going past the name has the text following it
+    let outer = node_id
+        .ancestors(ast_arena)
+        .map(|id| ast_arena[id].get())
+        .find(|node| node.range.end > end)?;
+    let line_index = LineIndex::new(&outer.content);
+    let start = outer.range.start;
+    let relative = if end.line == start.line {
+        Position::new(0, end.character.saturating_sub(start.character))
+    } else {
+        Position::new(end.line - start.line, end.character)
+    };
+    let rest = outer
+        .content
+        .get(line_index.offset(&outer.content, relative)..)?;
+    let arguments = skip_type_arguments(rest)?.trim_start().strip_prefix('(')?;
+
+    let (mut depth, mut commas) = (0, 0);
+    for (i, c) in arguments.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth > 0 => depth -= 1,
+            ')' if arguments[..i].trim().is_empty() => return Some(0),
+            ')' => return Some(commas + 1),
+            ',' if depth == 0 => commas += 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// The text after the type arguments starting it, e.g. `(x)` for `<bit<8>>(x)`.
+/// `None` if they aren't closed.
+fn skip_type_arguments(text: &str) -> Option<&str> {
+    let text = text.trim_start();
+    if !text.starts_with('<') {
+        return Some(text);
+    }
+
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return Some(&text[i + 1..]);
+        }
+    }
+
+    None
+}
+
+/// Weak references to the imported tables, to tell whether they changed.
+fn get_sources(imports: &Imports) -> Vec<(Url, Weak<SymbolTable>)> {
+    imports
+        .tables
+        .iter()
+        .map(|(uri, table)| (uri.clone(), Arc::downgrade(table)))
+        .collect()
+}
+
+/// Returns the highlight modifiers of a symbol type that hold for every
+/// occurrence of the symbol declared by `node_id`.
+fn get_declaration_modifiers(
+    kind: &str,
+    node_id: NodeId,
+    ast_arena: &Arena<Node>,
+) -> Vec<HighlightModifier> {
+    let Some(symbol_def) = LanguageDefinition::get().get_symbol_def(kind) else {
+        return vec![];
+    };
+
+    symbol_def
+        .highlight_modifiers
+        .iter()
+        .filter(|(_, condition)| match condition {
+            ModifierCondition::Always => true,
+            ModifierCondition::Child(..) => node_id.children(ast_arena).any(|child_id| {
+                let child = ast_arena.get(child_id).unwrap().get();
+                match &child.kind {
+                    NodeKind::Node(name) => condition.matches_child(name, &child.content),
+                    NodeKind::Error(_) => false,
+                }
+            }),
+            ModifierCondition::Definition | ModifierCondition::Library => false,
+        })
+        .map(|(modifier, _)| modifier.clone())
+        .collect()
+}
+
+/// Returns the text of the declaration `node_id`, the content of the scopes
+/// it opens (e.g. the body of an action) being elided.
+fn get_declaration_snippet(node_id: NodeId, ast_arena: &Arena<Node>) -> String {
+    let node = ast_arena.get(node_id).unwrap().get();
+    let line_index = LineIndex::new(&node.content);
+    let start = node.range.start;
+    let offset = |position: Position| {
+        let relative = if position.line == start.line {
+            Position::new(0, position.character.saturating_sub(start.character))
+        } else {
+            Position::new(position.line.saturating_sub(start.line), position.character)
+        };
+        line_index.offset(&node.content, relative)
+    };
+
+    let mut scope_ranges: Vec<Range> = node_id
+        .children(ast_arena)
+        .map(|id| ast_arena.get(id).unwrap().get())
+        .filter(|child| child.kind.is_scope_node())
+        .map(|child| child.range)
+        .collect();
+    scope_ranges.sort_by_key(|range| range.start);
+
+    let mut snippet = String::new();
+    let mut end = 0;
+    for range in scope_ranges {
+        let scope_start = offset(range.start);
+        if scope_start < end {
+            continue;
+        }
+        snippet.push_str(&node.content[end..scope_start]);
+        snippet.push_str("{ ... }");
+        end = offset(range.end);
+    }
+    snippet.push_str(&node.content[end..]);
+
+    snippet
+}
+
+/// Returns the direction given by a child of the declaration `node_id`, e.g. of a parameter.
+fn get_direction(node_id: NodeId, ast_arena: &Arena<Node>) -> Option<Direction> {
+    let direction_node = &LanguageDefinition::get().hover.as_ref()?.direction;
+
+    node_id.children(ast_arena).find_map(|id| {
+        let child = ast_arena.get(id).unwrap().get();
+        match &child.kind {
+            NodeKind::Node(name) if name == direction_node => Direction::parse(&child.content),
+            _ => None,
+        }
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct Symbol {
     name: String,
     kind: String,
+    uri: Url,
     type_symbol: Option<SymbolId>,
     def_position: Range,
     usages: Vec<Range>,
     field_scope_id: Option<ScopeId>,
+    /// Highlight modifiers given by the declaration, on every occurrence.
+    declaration_modifiers: Vec<HighlightModifier>,
+    /// Text of the type given by the declaration, e.g. `bit<8>`.
+    type_name: Option<String>,
+    /// Kind of type declared, for symbols naming a type.
+    type_declaration: Option<TypeDecType>,
+    /// Source of the declaration, without the content of its scopes.
+    declaration: String,
+    direction: Option<Direction>,
+    /// Declared by the language rather than the source, e.g. the final states of a parser.
+    builtin: bool,
 }
 
 impl Symbol {
-    pub fn new(name: String, kind: String, def_position: Range) -> Symbol {
+    pub fn new(name: String, kind: String, uri: Url, def_position: Range) -> Symbol {
         Symbol {
             name,
             kind,
+            uri,
             type_symbol: None,
             def_position,
             usages: vec![],
             field_scope_id: None,
+            declaration_modifiers: vec![],
+            type_name: None,
+            type_declaration: None,
+            declaration: String::new(),
+            direction: None,
+            builtin: false,
         }
     }
 
+    pub fn get_declaration_modifiers(&self) -> &Vec<HighlightModifier> {
+        &self.declaration_modifiers
+    }
+
     pub fn set_type_symbol(&mut self, id: SymbolId) {
         self.type_symbol = Some(id)
     }
@@ -504,6 +1213,26 @@ impl Symbol {
         self.type_symbol.clone()
     }
 
+    pub fn get_type_name(&self) -> Option<&str> {
+        self.type_name.as_deref()
+    }
+
+    pub fn get_type_declaration(&self) -> Option<TypeDecType> {
+        self.type_declaration
+    }
+
+    pub fn get_declaration(&self) -> &str {
+        &self.declaration
+    }
+
+    pub fn get_direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        self.builtin
+    }
+
     pub fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -512,6 +1241,10 @@ impl Symbol {
         self.def_position
     }
 
+    pub fn get_definition_location(&self) -> Location {
+        Location::new(self.uri.clone(), self.def_position)
+    }
+
     pub fn add_usage(&mut self, range: Range) {
         self.usages.push(range);
     }
@@ -527,6 +1260,26 @@ impl Symbol {
     pub fn get_field_scope_id(&self) -> Option<NodeId> {
         self.field_scope_id
     }
+
+    /// Hashes what the declaration of the symbol says, leaving out its usages.
+    fn hash_declaration(&self, hasher: &mut impl Hasher) {
+        self.name.hash(hasher);
+        self.kind.hash(hasher);
+        self.uri.hash(hasher);
+        for position in [self.def_position.start, self.def_position.end] {
+            position.line.hash(hasher);
+            position.character.hash(hasher);
+        }
+        self.field_scope_id.is_some().hash(hasher);
+        self.type_name.hash(hasher);
+        self.declaration.hash(hasher);
+        format!(
+            "{:?}{:?}{:?}",
+            self.declaration_modifiers, self.type_declaration, self.direction
+        )
+        .hash(hasher);
+        self.builtin.hash(hasher);
+    }
 }
 
 impl fmt::Display for Symbol {
@@ -0,0 +1,936 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use indextree::NodeId;
+use tower_lsp::lsp_types::{Position, Range};
+
+use crate::language_def::{self, DirectOrRule, LanguageDefinition, TypeChecking};
+use crate::metadata::{
+    Literal, NodeKind, Operator, SymbolTableQuery, TypeDecType, VisitNode, Visitable,
+};
+use crate::utils::{compact, is_identifier};
+
+/// Typedefs referring to typedefs deeper than this are considered cyclic.
+const MAX_TYPEDEF_DEPTH: usize = 16;
+
+/// Type of a value, as far as it can be inferred.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    Bit(u32),
+    Int(u32),
+    /// Integer of arbitrary precision, e.g. a literal without a width.
+    InfInt,
+    Varbit(u32),
+    Bool,
+    String,
+    Error,
+    Void,
+    Header(String),
+    HeaderUnion(String),
+    Struct(String),
+    Enum {
+        name: String,
+        underlying: Option<Box<Type>>,
+    },
+    Tuple(Vec<Type>),
+    Stack(Box<Type>, u32),
+    /// Type that couldn't be inferred, it is never reported as a mismatch.
+    Unknown,
+}
+
+impl Type {
+    /// Whether a value of type `value` can be stored in a location of this
+    /// type, as is or through an implicit cast.
+    pub fn accepts(&self, value: &Type) -> bool {
+        match (self, value) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::Bit(_) | Type::Int(_), Type::InfInt) => true,
+            (
+                target,
+                Type::Enum {
+                    underlying: Some(underlying),
+                    ..
+                },
+            ) if target == underlying.as_ref() => true,
+            (Type::Tuple(targets), Type::Tuple(values)) => {
+                targets.len() == values.len()
+                    && targets
+                        .iter()
+                        .zip(values)
+                        .all(|(target, value)| target.accepts(value))
+            }
+            // Initialization from a list of the fields' values
+            (Type::Header(_) | Type::HeaderUnion(_) | Type::Struct(_), Type::Tuple(_)) => true,
+            (target, value) => target == value,
+        }
+    }
+
+    /// Number of bits of a value of fixed width, e.g. a header field.
+    pub fn width(&self) -> Option<u32> {
+        match self {
+            Type::Bit(width) | Type::Int(width) => Some(*width),
+            Type::Bool => Some(1),
+            Type::Enum {
+                underlying: Some(underlying),
+                ..
+            } => underlying.width(),
+            _ => None,
+        }
+    }
+
+    /// Integer type of the value, serializable enums having their underlying type.
+    fn numeric(&self) -> Option<Type> {
+        match self {
+            Type::Bit(_) | Type::Int(_) | Type::InfInt => Some(self.clone()),
+            Type::Enum {
+                underlying: Some(underlying),
+                ..
+            } => underlying.numeric(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Bit(width) => write!(fmt, "bit<{}>", width),
+            Type::Int(width) => write!(fmt, "int<{}>", width),
+            Type::InfInt => fmt.write_str("int"),
+            Type::Varbit(width) => write!(fmt, "varbit<{}>", width),
+            Type::Bool => fmt.write_str("bool"),
+            Type::String => fmt.write_str("string"),
+            Type::Error => fmt.write_str("error"),
+            Type::Void => fmt.write_str("void"),
+            Type::Header(name) | Type::HeaderUnion(name) | Type::Struct(name) => {
+                fmt.write_str(name)
+            }
+            Type::Enum { name, .. } => fmt.write_str(name),
+            Type::Tuple(types) => write!(
+                fmt,
+                "tuple<{}>",
+                types
+                    .iter()
+                    .map(Type::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Type::Stack(element, size) => write!(fmt, "{}[{}]", element, size),
+            Type::Unknown => fmt.write_str("unknown"),
+        }
+    }
+}
+
+/// Type and value of an integer literal such as `42`, `0xFF` or `8w5`.
+pub fn parse_integer(text: &str) -> Option<(Type, i128)> {
+    let text: String = text.chars().filter(|c| *c != '_').collect();
+
+    let (integer_type, digits) = match text.find(['w', 's']) {
+        Some(i) => {
+            let width = text[..i].parse().ok()?;
+            let integer_type = if text[i..].starts_with('w') {
+                Type::Bit(width)
+            } else {
+                Type::Int(width)
+            };
+            (integer_type, text[i + 1..].to_ascii_lowercase())
+        }
+        None => (Type::InfInt, text.to_ascii_lowercase()),
+    };
+
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        Some("0d") => (10, &digits[2..]),
+        _ => (10, &digits[..]),
+    };
+
+    Some((integer_type, i128::from_str_radix(digits, radix).ok()?))
+}
+
+/// Type of `left operator right`, or `None` if the operator doesn't apply to the operands.
+pub fn binary_type(operator: Operator, left: &Type, right: &Type) -> Option<Type> {
+    let is_comparison = matches!(
+        operator,
+        Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessEqual
+            | Operator::Greater
+            | Operator::GreaterEqual
+            | Operator::And
+            | Operator::Or
+    );
+    if *left == Type::Unknown || *right == Type::Unknown {
+        return Some(if is_comparison {
+            Type::Bool
+        } else {
+            Type::Unknown
+        });
+    }
+
+    match operator {
+        Operator::Add
+        | Operator::Subtract
+        | Operator::Multiply
+        | Operator::Divide
+        | Operator::Modulo
+        | Operator::BitAnd
+        | Operator::BitOr
+        | Operator::BitXor => unify_numeric(left, right),
+        Operator::SaturatingAdd | Operator::SaturatingSubtract => {
+            unify_numeric(left, right).filter(|result| *result != Type::InfInt)
+        }
+        // The shift amount must be unsigned
+        Operator::ShiftLeft | Operator::ShiftRight => match (left.numeric()?, right.numeric()?) {
+            (_, Type::Int(_)) => None,
+            (left, _) => Some(left),
+        },
+        Operator::Concat => match (left.numeric()?, right.numeric()?) {
+            (Type::Bit(a), Type::Bit(b) | Type::Int(b)) => Some(Type::Bit(a + b)),
+            (Type::Int(a), Type::Bit(b) | Type::Int(b)) => Some(Type::Int(a + b)),
+            _ => None,
+        },
+        Operator::Equal | Operator::NotEqual => {
+            (left.accepts(right) || right.accepts(left) || unify_numeric(left, right).is_some())
+                .then_some(Type::Bool)
+        }
+        Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual => {
+            unify_numeric(left, right).map(|_| Type::Bool)
+        }
+        Operator::And | Operator::Or => {
+            (*left == Type::Bool && *right == Type::Bool).then_some(Type::Bool)
+        }
+        Operator::Complement | Operator::Not => None,
+    }
+}
+
+/// Type of `operator operand`, or `None` if the operator doesn't apply to the operand.
+pub fn unary_type(operator: Operator, operand: &Type) -> Option<Type> {
+    if *operand == Type::Unknown {
+        return Some(if operator == Operator::Not {
+            Type::Bool
+        } else {
+            Type::Unknown
+        });
+    }
+
+    match operator {
+        Operator::Not => (*operand == Type::Bool).then_some(Type::Bool),
+        Operator::Complement => match operand.numeric()? {
+            Type::InfInt => None,
+            operand => Some(operand),
+        },
+        Operator::Add | Operator::Subtract => operand.numeric(),
+        _ => None,
+    }
+}
+
+/// Common type of two integer operands, an arbitrary precision integer
+/// being implicitly cast to the type of the other operand.
+fn unify_numeric(left: &Type, right: &Type) -> Option<Type> {
+    match (left.numeric()?, right.numeric()?) {
+        (Type::InfInt, other) | (other, Type::InfInt) => Some(other),
+        (left, right) => (left == right).then_some(left),
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeError {
+    pub range: Range,
+    pub message: String,
+}
+
+/// Role of a node inside an expression.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Part {
+    Expression,
+    Name,
+    Member,
+    Operator(Option<Operator>),
+    Literal(Literal),
+    Other,
+}
+
+impl Part {
+    fn is_operand(&self) -> bool {
+        matches!(self, Part::Expression | Part::Name | Part::Literal(_))
+    }
+}
+
+/// Infers the types of the expressions of an AST and checks them where they
+/// are assigned, used to initialize a declaration or used as a condition.
+pub struct TypeChecker<'a, Q: SymbolTableQuery> {
+    config: &'static TypeChecking,
+    symbol_table: &'a Q,
+    errors: Vec<TypeError>,
+}
+
+impl<'a, Q: SymbolTableQuery> TypeChecker<'a, Q> {
+    /// Returns `None` if the language definition has no type checking rules.
+    pub fn new(symbol_table: &'a Q) -> Option<TypeChecker<'a, Q>> {
+        Some(TypeChecker {
+            config: LanguageDefinition::get().type_checking.as_ref()?,
+            symbol_table,
+            errors: vec![],
+        })
+    }
+
+    /// Returns the type errors of the tree under `root`, in order.
+    pub fn check(mut self, root: VisitNode) -> Vec<TypeError> {
+        let mut types = HashMap::new();
+        self.infer_all(root, &mut types);
+
+        for node in root.get_descendants() {
+            self.check_node(node, &types);
+        }
+
+        self.errors.sort_by_key(|error| error.range.start);
+        self.errors
+    }
+
+    fn infer_all(&mut self, node: VisitNode, types: &mut HashMap<NodeId, Type>) {
+        if self.part(&node) == Part::Expression {
+            let expression_type = self.infer(node);
+            types.insert(node.get_id(), expression_type);
+        } else {
+            for child in node.get_children() {
+                self.infer_all(child, types);
+            }
+        }
+    }
+
+    fn check_node(&mut self, node: VisitNode, types: &HashMap<NodeId, Type>) {
+        let NodeKind::Node(node_name) = &node.get().kind else {
+            return;
+        };
+        let children = self.get_parts(&node);
+        let Some((expression, _)) = children.iter().find(|(_, part)| *part == Part::Expression)
+        else {
+            return;
+        };
+        let value = types
+            .get(&expression.get_id())
+            .cloned()
+            .unwrap_or(Type::Unknown);
+        let range = expression.get().range;
+
+        if let Some((_, target_node)) = self
+            .config
+            .assignments
+            .iter()
+            .find(|(rule, _)| rule == node_name)
+        {
+            let Some(target) = node.get_child_of_kind(NodeKind::Node(target_node.clone())) else {
+                return;
+            };
+            let target_name = compact(&target.get().content);
+            let target_type = self.get_location_type(&target_name, target.get().range.start);
+
+            if !target_type.accepts(&value) {
+                self.report(
+                    range,
+                    format!(
+                        "Cannot assign a value of type `{}` to `{}` of type `{}`.",
+                        value, target_name, target_type
+                    ),
+                );
+            }
+        } else if self.config.initializers.contains(node_name) {
+            let language_def::Symbol::Init {
+                name_node,
+                type_node: Some(type_node),
+                ..
+            } = &node.get().symbol
+            else {
+                return;
+            };
+            let (Some(name), Some(declared)) = (
+                node.get_child_of_kind(NodeKind::Node(name_node.clone())),
+                node.get_child_of_kind(NodeKind::Node(type_node.clone())),
+            ) else {
+                return;
+            };
+            let declared_type = self.resolve_type(&declared.get().content, range.start);
+
+            if !declared_type.accepts(&value) {
+                self.report(
+                    range,
+                    format!(
+                        "Cannot initialize `{}` of type `{}` with a value of type `{}`.",
+                        name.get().content,
+                        declared_type,
+                        value
+                    ),
+                );
+            }
+        } else if self.config.conditions.contains(node_name) {
+            self.check_condition(range, &value);
+        }
+    }
+
+    fn check_condition(&mut self, range: Range, value: &Type) {
+        if !Type::Bool.accepts(value) {
+            self.report(
+                range,
+                format!("Expected a condition of type `bool`, found `{}`.", value),
+            );
+        }
+    }
+
+    fn report(&mut self, range: Range, message: String) {
+        self.errors.push(TypeError { range, message });
+    }
+
+    /// Type of an expression node, reporting the misuses of operators inside it.
+    pub fn infer(&mut self, node: VisitNode) -> Type {
+        let content = compact(&node.get().content);
+        let range = node.get().range;
+        let children = self.get_parts(&node);
+        let parts: Vec<Part> = children.iter().map(|(_, part)| *part).collect();
+        let text = |i: usize| compact(&children[i].0.get().content);
+
+        match parts.as_slice() {
+            [operand] if operand.is_operand() => {
+                let inner = text(0);
+                let inner_type = self.get_operand_type(children[0]);
+
+                if content == inner || content == format!("({})", inner) {
+                    inner_type
+                } else if let Some(rest) = content.strip_prefix(&inner) {
+                    // The arguments aren't part of the tree, a call has the
+                    // type returned by its callee.
+                    if rest.starts_with('(') {
+                        inner_type
+                    } else {
+                        Type::Unknown
+                    }
+                } else if let Some(prefix) = content.strip_suffix(&inner) {
+                    match prefix.strip_prefix('(').and_then(|p| p.strip_suffix(')')) {
+                        Some(cast_type) => self.resolve_type(cast_type, range.start),
+                        None => match Operator::parse(prefix) {
+                            Some(operator) => self.apply_unary(range, operator, &inner_type),
+                            None => Type::Unknown,
+                        },
+                    }
+                } else {
+                    Type::Unknown
+                }
+            }
+            [Part::Operator(operator), operand] if operand.is_operand() => {
+                let operand = self.get_operand_type(children[1]);
+                match operator {
+                    Some(operator) => self.apply_unary(range, *operator, &operand),
+                    None => Type::Unknown,
+                }
+            }
+            [left, Part::Operator(operator), right] if left.is_operand() && right.is_operand() => {
+                let left = self.get_operand_type(children[0]);
+                let right = self.get_operand_type(children[2]);
+                let Some(operator) = operator else {
+                    return Type::Unknown;
+                };
+
+                binary_type(*operator, &left, &right).unwrap_or_else(|| {
+                    self.report(
+                        range,
+                        format!(
+                            "Operator `{}` cannot be applied to `{}` and `{}`.",
+                            operator, left, right
+                        ),
+                    );
+                    Type::Unknown
+                })
+            }
+            [base, Part::Member] if base.is_operand() => {
+                let base_name = text(0);
+                let member = text(1);
+
+                // Members of a type, such as the values of an enum
+                if is_identifier(&base_name) {
+                    let base_type = self.resolve_type(&base_name, range.start);
+                    if matches!(base_type, Type::Enum { .. } | Type::Error) {
+                        return base_type;
+                    }
+                }
+
+                let base_type = self.get_operand_type(children[0]);
+                self.get_field_type(&base_type, &member, range.start)
+            }
+            [base, index] if base.is_operand() && index.is_operand() => {
+                let base = self.get_operand_type(children[0]);
+                self.get_operand_type(children[1]);
+
+                if content == format!("{}[{}]", text(0), text(1)) {
+                    get_element_type(&base)
+                } else {
+                    Type::Unknown
+                }
+            }
+            [_, _, _] if parts.iter().all(Part::is_operand) => {
+                let types: Vec<Type> = (0..3).map(|i| self.get_operand_type(children[i])).collect();
+
+                if content == format!("{}[{}:{}]", text(0), text(1), text(2)) {
+                    get_slice_type(&types[0], &text(1), &text(2))
+                } else if content == format!("{}?{}:{}", text(0), text(1), text(2)) {
+                    self.check_condition(children[0].0.get().range, &types[0]);
+
+                    match (&types[1], &types[2]) {
+                        (a, b) if a.accepts(b) && *a != Type::InfInt => a.clone(),
+                        (a, b) if b.accepts(a) => b.clone(),
+                        (a, b) => {
+                            self.report(
+                                range,
+                                format!("The branches have different types `{}` and `{}`.", a, b),
+                            );
+                            Type::Unknown
+                        }
+                    }
+                } else {
+                    Type::Unknown
+                }
+            }
+            _ => {
+                let types: Vec<Type> = children
+                    .iter()
+                    .filter(|(_, part)| part.is_operand())
+                    .map(|child| self.get_operand_type(*child))
+                    .collect();
+
+                let is_list = content.starts_with('{') && content.ends_with('}');
+                if is_list && types.len() == parts.len() && (content == "{}" || !types.is_empty()) {
+                    Type::Tuple(types)
+                } else {
+                    Type::Unknown
+                }
+            }
+        }
+    }
+
+    fn get_operand_type(&mut self, (node, part): (VisitNode, Part)) -> Type {
+        match part {
+            Part::Expression => self.infer(node),
+            Part::Name => self.get_name_type(node),
+            Part::Literal(Literal::Integer) => {
+                parse_integer(&compact(&node.get().content)).map_or(Type::Unknown, |(t, _)| t)
+            }
+            Part::Literal(Literal::Bool) => Type::Bool,
+            Part::Literal(Literal::String) => Type::String,
+            _ => Type::Unknown,
+        }
+    }
+
+    fn apply_unary(&mut self, range: Range, operator: Operator, operand: &Type) -> Type {
+        unary_type(operator, operand).unwrap_or_else(|| {
+            self.report(
+                range,
+                format!(
+                    "Operator `{}` cannot be applied to `{}`.",
+                    operator, operand
+                ),
+            );
+            Type::Unknown
+        })
+    }
+
+    /// Children of a node and their role, in order. Nodes added anywhere by
+    /// the global rules, such as comments, are left out.
+    fn get_parts<'b>(&self, node: &'b VisitNode) -> Vec<(VisitNode<'b>, Part)> {
+        let global_nodes: Vec<&String> = LanguageDefinition::get()
+            .global_ast_rules
+            .iter()
+            .map(|child| match &child.rule {
+                DirectOrRule::Direct(name) | DirectOrRule::Rule(name) => name,
+            })
+            .collect();
+
+        let mut children: Vec<VisitNode> = node
+            .get_children()
+            .into_iter()
+            .filter(|child| match &child.get().kind {
+                NodeKind::Node(name) => !global_nodes.contains(&name),
+                NodeKind::Error(_) => true,
+            })
+            .collect();
+        children.sort_by_key(|child| child.get().range.start);
+
+        children
+            .into_iter()
+            .map(|child| (child, self.part(&child)))
+            .collect()
+    }
+
+    fn part(&self, node: &VisitNode) -> Part {
+        let data = node.get();
+        match data.symbol {
+            language_def::Symbol::Expression => return Part::Expression,
+            language_def::Symbol::Usage => return Part::Name,
+            language_def::Symbol::MemberUsage => return Part::Member,
+            _ => {}
+        }
+
+        let NodeKind::Node(name) = &data.kind else {
+            return Part::Other;
+        };
+        if *name == self.config.operator {
+            return Part::Operator(Operator::parse(&data.content));
+        }
+
+        self.config
+            .literals
+            .iter()
+            .find(|(literal_node, _)| literal_node == name)
+            .map_or(Part::Other, |(_, literal)| Part::Literal(*literal))
+    }
+
+    /// Type of the value named by a usage node.
+    fn get_name_type(&self, node: VisitNode) -> Type {
+        let Some(symbol) = node
+            .get()
+            .linked_symbol
+            .clone()
+            .and_then(|id| self.symbol_table.get_symbol(id))
+        else {
+            return Type::Unknown;
+        };
+
+        match (symbol.get_type_declaration(), symbol.get_type_name()) {
+            (None, Some(type_name)) => self.resolve_type(type_name, node.get().range.start),
+            _ => Type::Unknown,
+        }
+    }
+
+    /// Type of a location such as `hdr.ipv4.ttl` or `meta.stack[0].field`.
+    fn get_location_type(&self, location: &str, position: Position) -> Type {
+        let head_end = location.find(['.', '[']).unwrap_or(location.len());
+        let Some(symbol) = self
+            .symbol_table
+            .get_symbol_at_pos(location[..head_end].to_string(), position)
+        else {
+            return Type::Unknown;
+        };
+        let mut location_type = match (symbol.get_type_declaration(), symbol.get_type_name()) {
+            (None, Some(type_name)) => self.resolve_type(type_name, position),
+            _ => return Type::Unknown,
+        };
+
+        let mut rest = &location[head_end..];
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                location_type = self.get_field_type(&location_type, &after[..end], position);
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let Some(end) = after.find(']') else {
+                    return Type::Unknown;
+                };
+                location_type = match after[..end].split_once(':') {
+                    Some((high, low)) => get_slice_type(&location_type, high, low),
+                    None => get_element_type(&location_type),
+                };
+                rest = &after[end + 1..];
+            } else {
+                return Type::Unknown;
+            }
+        }
+
+        location_type
+    }
+
+    fn get_field_type(&self, base: &Type, field: &str, position: Position) -> Type {
+        let (Type::Header(name) | Type::HeaderUnion(name) | Type::Struct(name)) = base else {
+            return Type::Unknown;
+        };
+
+        let field_symbol = self
+            .symbol_table
+            .get_symbol_at_pos(name.clone(), position)
+            .and_then(|symbol| symbol.get_field_scope_id())
+            .and_then(|scope_id| {
+                self.symbol_table
+                    .get_symbols_in_scope(scope_id)
+                    .into_iter()
+                    .find(|symbol| symbol.get_name() == field)
+            });
+
+        match field_symbol
+            .as_ref()
+            .and_then(|symbol| symbol.get_type_name())
+        {
+            Some(type_name) => self.resolve_type(type_name, position),
+            None => Type::Unknown,
+        }
+    }
+
+    /// Type written as `text`, with the declared types visible at `position`.
+    pub fn resolve_type(&self, text: &str, position: Position) -> Type {
+        self.resolve_type_at_depth(&compact(text), position, 0)
+    }
+
+    fn resolve_type_at_depth(&self, text: &str, position: Position, depth: usize) -> Type {
+        if depth > MAX_TYPEDEF_DEPTH {
+            return Type::Unknown;
+        }
+
+        match text {
+            "bool" => return Type::Bool,
+            "string" => return Type::String,
+            "error" => return Type::Error,
+            "void" => return Type::Void,
+            "int" => return Type::InfInt,
+            "bit" => return Type::Bit(1),
+            _ => {}
+        }
+
+        let generic_argument = |name: &str| {
+            text.strip_prefix(name)?
+                .strip_prefix('<')?
+                .strip_suffix('>')
+                .map(str::to_string)
+        };
+        let width = |argument: String| {
+            parse_integer(&argument)
+                .and_then(|(_, value)| u32::try_from(value).ok())
+                .filter(|width| *width > 0)
+        };
+
+        if let Some(argument) = generic_argument("bit") {
+            return width(argument).map_or(Type::Unknown, Type::Bit);
+        }
+        if let Some(argument) = generic_argument("int") {
+            return width(argument).map_or(Type::Unknown, Type::Int);
+        }
+        if let Some(argument) = generic_argument("varbit") {
+            return width(argument).map_or(Type::Unknown, Type::Varbit);
+        }
+        if let Some(arguments) = generic_argument("tuple") {
+            return Type::Tuple(
+                split_arguments(&arguments)
+                    .into_iter()
+                    .map(|argument| self.resolve_type_at_depth(argument, position, depth + 1))
+                    .collect(),
+            );
+        }
+        if let Some((element, size)) = text
+            .strip_suffix(']')
+            .and_then(|text| text.rsplit_once('['))
+        {
+            let element = self.resolve_type_at_depth(element, position, depth + 1);
+            return match parse_integer(size).and_then(|(_, size)| u32::try_from(size).ok()) {
+                Some(size) => Type::Stack(Box::new(element), size),
+                None => Type::Unknown,
+            };
+        }
+
+        if !is_identifier(text) {
+            return Type::Unknown;
+        }
+        let Some(symbol) = self
+            .symbol_table
+            .get_symbol_at_pos(text.to_string(), position)
+        else {
+            return Type::Unknown;
+        };
+
+        let name = text.to_string();
+        match symbol.get_type_declaration() {
+            Some(TypeDecType::HeaderType) => Type::Header(name),
+            Some(TypeDecType::HeaderUnion) => Type::HeaderUnion(name),
+            Some(TypeDecType::Struct) => Type::Struct(name),
+            Some(TypeDecType::Enum) => Type::Enum {
+                name,
+                underlying: symbol.get_type_name().map(|underlying| {
+                    Box::new(self.resolve_type_at_depth(&compact(underlying), position, depth + 1))
+                }),
+            },
+            Some(TypeDecType::TypeDef) => symbol.get_type_name().map_or(Type::Unknown, |aliased| {
+                self.resolve_type_at_depth(&compact(aliased), position, depth + 1)
+            }),
+            _ => Type::Unknown,
+        }
+    }
+}
+
+fn get_element_type(base: &Type) -> Type {
+    match base {
+        Type::Stack(element, _) => *element.clone(),
+        _ => Type::Unknown,
+    }
+}
+
+/// Type of the bits `high` to `low` of a value, the bounds being literals.
+fn get_slice_type(base: &Type, high: &str, low: &str) -> Type {
+    if base.numeric().is_none() {
+        return Type::Unknown;
+    }
+
+    match (parse_integer(high), parse_integer(low)) {
+        (Some((_, high)), Some((_, low))) if low >= 0 && high >= low => {
+            u32::try_from(high - low + 1).map_or(Type::Unknown, Type::Bit)
+        }
+        _ => Type::Unknown,
+    }
+}
+
+/// Splits the arguments of a generic type on the commas outside of nested arguments.
+fn split_arguments(arguments: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in arguments.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&arguments[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&arguments[start..]);
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Url;
+
+    use super::{binary_type, parse_integer, unary_type, Type, TypeChecker};
+    use crate::file::tests::new_parser;
+    use crate::metadata::{AstManager, AstQuery, Imports, Operator, SymbolTableManager};
+
+    #[test]
+    fn test_parse_integer() {
+        assert_eq!(parse_integer("42"), Some((Type::InfInt, 42)));
+        assert_eq!(parse_integer("0xFF"), Some((Type::InfInt, 255)));
+        assert_eq!(parse_integer("8w5"), Some((Type::Bit(8), 5)));
+        assert_eq!(parse_integer("16s0x1_0"), Some((Type::Int(16), 16)));
+        assert_eq!(parse_integer("4w0b1010"), Some((Type::Bit(4), 10)));
+        assert_eq!(parse_integer("x"), None);
+    }
+
+    #[test]
+    fn test_binary_type() {
+        let bit8 = Type::Bit(8);
+
+        assert_eq!(binary_type(Operator::Add, &bit8, &bit8), Some(bit8.clone()));
+        assert_eq!(
+            binary_type(Operator::Add, &Type::InfInt, &bit8),
+            Some(bit8.clone())
+        );
+        assert_eq!(binary_type(Operator::Add, &bit8, &Type::Bit(16)), None);
+        assert_eq!(binary_type(Operator::Add, &bit8, &Type::Bool), None);
+        assert_eq!(
+            binary_type(Operator::Concat, &bit8, &Type::Int(4)),
+            Some(Type::Bit(12))
+        );
+        assert_eq!(binary_type(Operator::Concat, &bit8, &Type::InfInt), None);
+        assert_eq!(binary_type(Operator::ShiftLeft, &bit8, &Type::Int(4)), None);
+        assert_eq!(
+            binary_type(Operator::Equal, &bit8, &Type::InfInt),
+            Some(Type::Bool)
+        );
+        assert_eq!(
+            binary_type(
+                Operator::Equal,
+                &Type::Struct("s".to_string()),
+                &Type::Struct("s".to_string())
+            ),
+            Some(Type::Bool)
+        );
+        assert_eq!(binary_type(Operator::Less, &Type::Bool, &Type::Bool), None);
+        assert_eq!(binary_type(Operator::And, &Type::Bool, &bit8), None);
+        assert_eq!(
+            binary_type(Operator::Or, &Type::Unknown, &bit8),
+            Some(Type::Bool)
+        );
+        assert_eq!(
+            binary_type(Operator::Add, &Type::Unknown, &Type::Bool),
+            Some(Type::Unknown)
+        );
+    }
+
+    #[test]
+    fn test_unary_type() {
+        assert_eq!(unary_type(Operator::Not, &Type::Bool), Some(Type::Bool));
+        assert_eq!(unary_type(Operator::Not, &Type::Bit(1)), None);
+        assert_eq!(
+            unary_type(Operator::Complement, &Type::Bit(4)),
+            Some(Type::Bit(4))
+        );
+        assert_eq!(unary_type(Operator::Complement, &Type::InfInt), None);
+        assert_eq!(
+            unary_type(Operator::Subtract, &Type::InfInt),
+            Some(Type::InfInt)
+        );
+    }
+
+    #[test]
+    fn test_accepts() {
+        let color = Type::Enum {
+            name: "color_t".to_string(),
+            underlying: Some(Box::new(Type::Bit(2))),
+        };
+
+        assert!(Type::Bit(9).accepts(&Type::InfInt));
+        assert!(!Type::Bit(9).accepts(&Type::Bit(16)));
+        assert!(!Type::InfInt.accepts(&Type::Bit(16)));
+        assert!(Type::Bit(2).accepts(&color));
+        assert!(!color.accepts(&Type::Bit(2)));
+        assert!(Type::Struct("s".to_string()).accepts(&Type::Tuple(vec![Type::Bool])));
+        assert!(!Type::Bool.accepts(&Type::Bit(1)));
+        assert!(Type::Bool.accepts(&Type::Unknown));
+    }
+
+    #[test]
+    fn test_check() {
+        let source = "header h_t {
+    bit<9> port;
+    bit<16> len;
+}
+
+struct headers_t {
+    h_t h;
+}
+
+control C(inout headers_t hdr, in bit<16> x) {
+    apply {
+        bit<8> small = 8w1 + 8w2;
+        bool flag = 1;
+        hdr.h.port = x;
+        hdr.h.len = x + 1;
+        if (x) {
+            hdr.h.len = hdr.h.len ++ 8w0;
+        }
+    }
+}
+";
+        let mut parser = new_parser();
+        let tree = parser.parse(source, None).unwrap();
+        let uri = Url::parse("file:///test.p4").unwrap();
+
+        let mut ast_manager = AstManager::new(source, tree);
+        let st_manager = SymbolTableManager::new(uri, ast_manager.get_ast(), &Imports::default());
+
+        let messages: Vec<String> = TypeChecker::new(&st_manager)
+            .unwrap()
+            .check(ast_manager.visit_root())
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                "Cannot initialize `flag` of type `bool` with a value of type `int`.",
+                "Cannot assign a value of type `bit<16>` to `hdr.h.port` of type `bit<9>`.",
+                "Expected a condition of type `bool`, found `bit<16>`.",
+                "Cannot assign a value of type `bit<24>` to `hdr.h.len` of type `bit<16>`.",
+            ]
+        );
+    }
+}
@@ -65,17 +65,16 @@ impl PluginManager {
     }
     pub fn load_plugins(&mut self, uri: Option<Url>, json_str: &str) {
         if let Ok(mut plugins) = from_str::<Vec<Plugin>>(json_str) {
-            if let Some(url) = uri {
+            let workspace_path = uri
+                .and_then(|url| url.to_file_path().ok())
+                .and_then(|path| path.into_os_string().into_string().ok());
+
+            if let Some(path) = workspace_path {
                 let key = String::from("workspace");
                 for plugin in plugins.iter_mut() {
                     plugin.arguments.push(Argument {
                         key: key.clone(),
-                        value: url
-                            .to_file_path()
-                            .unwrap()
-                            .into_os_string()
-                            .into_string()
-                            .unwrap(),
+                        value: path.clone(),
                     })
                 }
             }
@@ -86,29 +85,49 @@ impl PluginManager {
 
     pub fn run_plugins(&mut self, file: Url, state: OnState) -> PluginsResult {
         let mut plugins_result: PluginsResult = PluginsResult::new();
+        let Some(file_path) = file
+            .to_file_path()
+            .ok()
+            .and_then(|path| path.into_os_string().into_string().ok())
+        else {
+            return plugins_result;
+        };
+
         for plugin in self.plugins.clone().iter_mut() {
             let key = String::from("file");
             plugin.arguments.push(Argument {
                 key: key.clone(),
-                value: file
-                    .to_file_path()
-                    .unwrap()
-                    .into_os_string()
-                    .into_string()
-                    .unwrap(),
+                value: file_path.clone(),
             });
             if plugin.on.contains(&state) {
-                let json_str = self.execute(plugin.clone()).unwrap();
-                let results: CustomResult = from_str(json_str.as_str()).unwrap();
+                let Some(json_str) = self.execute(plugin.clone()) else {
+                    warn!("Plugin {} couldn't be executed", plugin.name);
+                    continue;
+                };
+                let results: CustomResult = match from_str(json_str.as_str()) {
+                    Ok(results) => results,
+                    Err(e) => {
+                        warn!("Invalid output from plugin {}: {}", plugin.name, e);
+                        continue;
+                    }
+                };
 
                 match results.output_type {
                     TypesNotification::Diagnostic => {
-                        let mut diag: Vec<Diagnostic> = from_str(results.data.as_str()).unwrap();
-                        plugins_result.diagnostic.append(&mut diag);
+                        match from_str::<Vec<Diagnostic>>(results.data.as_str()) {
+                            Ok(mut diag) => plugins_result.diagnostic.append(&mut diag),
+                            Err(e) => {
+                                warn!("Invalid diagnostics from plugin {}: {}", plugin.name, e)
+                            }
+                        }
                     }
                     TypesNotification::Notification => {
-                        let notification: CustomParams = from_str(results.data.as_str()).unwrap();
-                        plugins_result.notification.push(notification);
+                        match from_str::<CustomParams>(results.data.as_str()) {
+                            Ok(notification) => plugins_result.notification.push(notification),
+                            Err(e) => {
+                                warn!("Invalid notification from plugin {}: {}", plugin.name, e)
+                            }
+                        }
                     }
                     TypesNotification::Nothing => {}
                 }
@@ -125,20 +144,20 @@ impl PluginManager {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .spawn()
-            .unwrap();
+            .ok()?;
 
         // Write data to the child process's stdin
         if let Some(mut stdin) = child.stdin.take() {
             let arguments = plugin.arguments;
-            let json_str = to_string(&arguments).unwrap();
+            let json_str = to_string(&arguments).ok()?;
             info!("a-{}", json_str);
-            stdin.write_all(json_str.as_bytes()).unwrap();
+            stdin.write_all(json_str.as_bytes()).ok()?;
             info!("b");
         }
 
         // Wait for the child process to finish and capture its stdout
         let result = match child.wait_with_output() {
-            Ok(output) => String::from_utf8(output.stdout).unwrap(),
+            Ok(output) => String::from_utf8(output.stdout).ok()?,
             Err(e) => e.to_string(),
         };
         Some(result)
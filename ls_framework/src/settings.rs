@@ -1,15 +1,58 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
 use serde_json::Value;
 
-#[derive(Debug, Default)]
-pub struct Settings {}
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub include_paths: Vec<PathBuf>,
+    pub diagnostics: DiagnosticSettings,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiagnosticSettings {
+    /// Warn when a declaration hides a name of an enclosing scope.
+    pub shadowing: bool,
+    pub unused: UnusedSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UnusedSettings {
+    pub enabled: bool,
+    /// Symbol kinds (as named in the language definition) never reported as unused.
+    pub ignored_kinds: Vec<String>,
+    /// Symbol names never reported as unused.
+    pub ignored_names: Vec<String>,
+}
+
+impl Default for UnusedSettings {
+    fn default() -> Self {
+        UnusedSettings {
+            enabled: true,
+            // Unused states are reported as unreachable
+            ignored_kinds: vec![
+                "Field".to_string(),
+                "Parameter".to_string(),
+                "State".to_string(),
+            ],
+            ignored_names: vec!["main".to_string()],
+        }
+    }
+}
 
 impl Settings {
     pub fn parse(value: Value) -> Settings {
-        if let Value::Object(_map) = value {
-            Settings {}
+        if let Value::Object(_) = value {
+            serde_json::from_value(value).unwrap_or_else(|e| {
+                warn!("Invalid settings: {}", e);
+                Settings::default()
+            })
         } else {
             Settings {
-                // ..Default::default()
+                ..Default::default()
             }
         }
     }
@@ -1,90 +1,77 @@
-use tower_lsp::lsp_types::Position;
-use tree_sitter::Point;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 
-pub fn pos_to_point(pos: Position) -> Point {
-    Point {
-        row: pos.line as usize,
-        column: pos.character as usize,
-    }
+pub fn get_node_text(node: &tree_sitter::Node, source_code: &str) -> String {
+    node.utf8_text(source_code.as_bytes()).unwrap().to_string()
 }
 
-pub fn point_to_pos(point: Point) -> Position {
-    Position {
-        line: point.row as u32,
-        character: point.column as u32,
-    }
-}
+/// Recursively lists the files of `dir` with one of the given extensions,
+/// skipping hidden directories and without following symbolic links.
+pub fn find_source_files(dir: &Path, extensions: &[String]) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
 
-pub fn ts_range_to_lsp_range(range: tree_sitter::Range) -> tower_lsp::lsp_types::Range {
-    tower_lsp::lsp_types::Range {
-        start: point_to_pos(range.start_point),
-        end: point_to_pos(range.end_point),
-    }
-}
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
 
-pub fn pos_to_byte(pos: Position, text: &str) -> usize {
-    let mut total_bytes = 0;
-    let lines = &text.lines().collect::<Vec<&str>>()[..(pos.line as usize)];
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
 
-    for line in lines {
-        total_bytes += line.len() + 1; // WARNING: Could break if line break char is not 1 byte
+            if file_type.is_dir() {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    dirs.push(path);
+                }
+            } else if file_type.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| extensions.iter().any(|e| *e == *ext.to_string_lossy()))
+            {
+                files.push(path);
+            }
+        }
     }
-    total_bytes + pos.character as usize
-}
-
-pub fn calculate_end_point(start: Point, new_content: &str) -> Point {
-    let new_lines: Vec<&str> = new_content.lines().collect();
-    let nb_lines = if new_lines.is_empty() {
-        1
-    } else {
-        new_lines.len()
-    };
 
-    let column = match new_lines.len() {
-        0 => start.column,
-        1 => start.column + new_content.len(),
-        _ => new_lines.last().unwrap().len(),
-    };
-
-    Point {
-        column,
-        row: start.row + nb_lines,
-    }
+    files.sort();
+    files
 }
 
-pub fn get_node_text(node: &tree_sitter::Node, source_code: &str) -> String {
-    node.utf8_text(source_code.as_bytes()).unwrap().to_string()
+/// Text without whitespace, so that texts can be compared whatever their formatting.
+pub fn compact(text: &str) -> String {
+    text.split_whitespace().collect()
 }
 
-#[cfg(test)]
-mod tests {
-    use tower_lsp::lsp_types::Position;
+/// Text on a single line, runs of whitespace being replaced by a space.
+pub fn compact_spaces(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
 
-    use super::pos_to_byte;
+/// Whether a text is a single name, as opposed to e.g. a built-in type like `bit<8>`.
+pub fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
 
-    #[test]
-    fn test_pos_to_byte() {
-        let string = "this\nis\na test\nfor this function";
+/// Whether a text is a name followed by members, e.g. `hdr.ipv4.dstAddr`.
+pub fn is_dotted_identifier(text: &str) -> bool {
+    text.split('.').all(is_identifier)
+}
 
-        assert_eq!(
-            pos_to_byte(
-                Position {
-                    character: 3,
-                    line: 2
-                },
-                string
-            ),
-            11
-        );
-        assert_eq!(
-            pos_to_byte(
-                Position {
-                    character: 5,
-                    line: 0
-                },
-                string
-            ),
-            5
-        );
-    }
+/// Runs `f`, returning the message of the panic if it panics.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown error".to_string()
+        }
+    })
 }
@@ -1,21 +1,46 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::{env, fs};
 
 use serde_json::Value;
 use tower_lsp::lsp_types::{
-    CompletionContext, CompletionItem, Diagnostic, HoverContents, Location, Position,
-    SemanticTokensResult, TextDocumentContentChangeEvent, Url, WorkspaceEdit,
+    CompletionContext, CompletionItem, Diagnostic, DocumentSymbol, HoverContents, Location,
+    NumberOrString, Position, Range, SemanticTokens, SemanticTokensFullDeltaResult, SignatureHelp,
+    SymbolInformation, TextDocumentContentChangeEvent, Url, WorkspaceEdit,
 };
 
-use crate::{file::File, settings::Settings};
+use crate::{
+    features::{diagnostics, references, rename, workspace_symbols},
+    file::File,
+    include::{self, Include},
+    language_def::LanguageDefinition,
+    metadata::{Imports, SymbolTableQuery},
+    settings::Settings,
+    utils,
+};
 
 pub struct Workspace {
     settings: Settings,
     files: HashMap<Url, File>,
     parser: tree_sitter::Parser,
+    bundled_includes: &'static [(&'static str, &'static str)],
+    bundled_include_dir: Option<PathBuf>,
+    roots: Vec<PathBuf>,
+    /// Versions of the files owned by the editor, their content doesn't come from the disk.
+    open_files: HashMap<Url, i32>,
+    /// Files edited since their symbol table was built.
+    stale_files: HashSet<Url>,
+    /// Files whose exports changed since the files including them were rebuilt.
+    changed_exports: HashSet<Url>,
+    /// Definition ranges of the imported symbols each file uses, by the file defining them.
+    imported_usages: HashMap<Url, HashMap<Url, Vec<Range>>>,
 }
 
 impl Workspace {
-    pub fn new(ts_language: tree_sitter::Language) -> Workspace {
+    pub fn new(
+        ts_language: tree_sitter::Language,
+        bundled_includes: &'static [(&'static str, &'static str)],
+    ) -> Workspace {
         let mut parser = tree_sitter::Parser::new();
         parser.set_language(ts_language).unwrap();
 
@@ -23,20 +48,402 @@ impl Workspace {
             settings: Settings::default(),
             files: HashMap::new(),
             parser,
+            bundled_includes,
+            bundled_include_dir: None,
+            roots: vec![],
+            open_files: HashMap::new(),
+            stale_files: HashSet::new(),
+            changed_exports: HashSet::new(),
+            imported_usages: HashMap::new(),
         }
     }
 
-    pub fn add_file(&mut self, url: Url, content: &str) {
-        let tree = self.parser.parse(content, None);
+    pub fn install_bundled_includes(&mut self) {
+        if self.bundled_includes.is_empty() {
+            return;
+        }
 
-        self.files
-            .insert(url.clone(), File::new(url, content, &tree));
+        let prefix = format!(
+            "{}-lsf-include",
+            LanguageDefinition::get().language.name.to_lowercase()
+        );
+
+        match include::install_bundled_files(&env::temp_dir(), &prefix, self.bundled_includes) {
+            Ok(dir) => {
+                // Resolved includes are canonical paths, so is the directory they are compared to
+                let dir = dir.canonicalize().unwrap_or(dir);
+                info!("Bundled includes installed in {}", dir.display());
+                self.bundled_include_dir = Some(dir);
+            }
+            Err(e) => error!("Couldn't install bundled includes: {}", e),
+        }
+    }
+
+    /// Deletes the bundled library files installed for this process.
+    pub fn remove_bundled_includes(&mut self) {
+        if let Some(dir) = self.bundled_include_dir.take() {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                warn!("Couldn't remove {}: {}", dir.display(), e);
+            }
+        }
+    }
+
+    /// Files whose symbols are library symbols: the bundled library files, and
+    /// the files of the search paths named like them (e.g. the architecture
+    /// files of an installed compiler).
+    fn get_library_files(&self) -> Vec<PathBuf> {
+        let dirs = self
+            .settings
+            .include_paths
+            .iter()
+            .map(|dir| dir.canonicalize().unwrap_or_else(|_| dir.clone()))
+            .chain(self.bundled_include_dir.clone());
+
+        dirs.flat_map(|dir| {
+            self.bundled_includes
+                .iter()
+                .map(move |(name, _)| dir.join(name))
+        })
+        .collect()
+    }
+
+    fn is_bundled(&self, url: &Url) -> bool {
+        match (&self.bundled_include_dir, url.to_file_path()) {
+            (Some(dir), Ok(path)) => path.starts_with(dir),
+            _ => false,
+        }
+    }
+
+    pub fn set_roots(&mut self, roots: Vec<PathBuf>) {
+        self.roots = roots;
+    }
+
+    /// Lists the files of the workspace folders that aren't known yet, so they
+    /// can be indexed and workspace-wide features don't depend on which files are open.
+    pub fn find_unindexed_files(&self) -> Vec<Url> {
+        let extensions = &LanguageDefinition::get().language.file_extensions;
+
+        self.roots
+            .iter()
+            .flat_map(|root| utils::find_source_files(root, extensions))
+            .filter_map(|path| Url::from_file_path(path.canonicalize().unwrap_or(path)).ok())
+            .filter(|url| !self.files.contains_key(url))
+            .collect()
+    }
+
+    pub fn index_file(&mut self, url: Url) {
+        if !self.files.contains_key(&url) {
+            self.load_file_from_disk(url.clone(), &mut HashSet::from([url]));
+        }
+    }
+
+    /// Reloads a file created or modified on disk, unless the editor owns it.
+    /// Returns the other files whose symbol tables were rebuilt.
+    pub fn reload_file(&mut self, url: Url) -> Vec<Url> {
+        if self.open_files.contains_key(&url) {
+            return vec![];
+        }
+
+        let is_new = !self.files.contains_key(&url);
+        self.load_file_from_disk(url.clone(), &mut HashSet::from([url.clone()]));
+        let mut rebuilt = self.refresh_dependents(&url);
+
+        if is_new {
+            // The new file may be what some includes were missing
+            let unresolved: Vec<Url> = self
+                .files
+                .values()
+                .filter(|file| file.includes.iter().any(|i| i.resolved.is_none()))
+                .map(|file| file.uri.clone())
+                .collect();
+
+            for url in unresolved {
+                if self.rebuild_symbol_table(&url) {
+                    rebuilt.extend(self.refresh_dependents(&url));
+                }
+                rebuilt.push(url);
+            }
+        }
+
+        rebuilt
+    }
+
+    /// Forgets a file deleted from disk, unless the editor owns it. Returns the
+    /// files whose symbol tables were rebuilt.
+    pub fn remove_file(&mut self, url: Url) -> Vec<Url> {
+        if self.open_files.contains_key(&url) || self.files.remove(&url).is_none() {
+            return vec![];
+        }
+        self.imported_usages.remove(&url);
+
+        self.refresh_dependents(&url)
+    }
+
+    /// Returns the other files whose symbol tables were rebuilt.
+    pub fn add_file(&mut self, url: Url, content: &str, version: i32) -> Vec<Url> {
+        self.open_files.insert(url.clone(), version);
+
+        let mut visiting = HashSet::from([url.clone()]);
+        self.insert_file(url.clone(), content, &mut visiting);
+
+        self.refresh_dependents(&url)
+    }
+
+    /// Hands a file back to the disk when the editor closes it: files of the
+    /// workspace (or included by one of them) revert to their saved content,
+    /// others are dropped. Returns the files whose symbol tables were rebuilt.
+    pub fn close_file(&mut self, url: Url) -> Vec<Url> {
+        if self.open_files.remove(&url).is_none() {
+            return vec![];
+        }
+
+        let on_disk = url.to_file_path().is_ok_and(|path| path.is_file());
+        if on_disk && (self.is_in_roots(&url) || self.is_included(&url)) {
+            self.load_file_from_disk(url.clone(), &mut HashSet::from([url.clone()]));
+        } else {
+            self.files.remove(&url);
+            self.stale_files.remove(&url);
+            self.imported_usages.remove(&url);
+        }
+
+        self.refresh_dependents(&url)
     }
 
-    pub fn update_file(&mut self, url: Url, changes: Vec<TextDocumentContentChangeEvent>) {
-        let file = self.files.get_mut(&url).unwrap();
+    fn is_in_roots(&self, url: &Url) -> bool {
+        let Ok(path) = url.to_file_path() else {
+            return false;
+        };
+
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    fn is_included(&self, url: &Url) -> bool {
+        self.files.values().any(|file| {
+            file.includes
+                .iter()
+                .any(|include| include.resolved.as_ref() == Some(url))
+        })
+    }
+
+    /// Returns the version of a file opened in the editor.
+    pub fn get_version(&self, url: &Url) -> Option<i32> {
+        self.open_files.get(url).copied()
+    }
+
+    /// Applies the changes to the text and syntax tree of a file. Its symbol
+    /// table is only rebuilt by [`Workspace::update_symbols`] or
+    /// [`Workspace::analyze_file`], so consecutive edits are analyzed once.
+    pub fn update_file(
+        &mut self,
+        url: Url,
+        version: i32,
+        changes: Vec<TextDocumentContentChangeEvent>,
+    ) {
+        let Some(file) = self.files.get_mut(&url) else {
+            warn!("Received changes for an unknown file: {}", url);
+            return;
+        };
+
+        self.open_files.insert(url.clone(), version);
 
         file.update(changes, &mut self.parser);
+        self.stale_files.insert(url);
+    }
+
+    /// Whether a file was edited since its symbol table was built.
+    pub fn is_stale(&self, url: &Url) -> bool {
+        self.stale_files.contains(url)
+    }
+
+    /// Rebuilds the symbol table of a file edited since it was built. The files
+    /// including it are left to [`Workspace::analyze_file`].
+    pub fn update_symbols(&mut self, url: &Url) {
+        if self.stale_files.contains(url) && self.rebuild_symbol_table(url) {
+            self.changed_exports.insert(url.clone());
+        }
+    }
+
+    /// Brings the symbol table of a file and of the files including it up to
+    /// date. Returns the other files whose symbol tables were rebuilt.
+    pub fn analyze_file(&mut self, url: &Url) -> Vec<Url> {
+        self.update_symbols(url);
+
+        if self.changed_exports.contains(url) {
+            self.refresh_dependents(url)
+        } else {
+            vec![]
+        }
+    }
+
+    /// Rebuilds from their text the files a failure may have left in an
+    /// inconsistent state: the given file and the files with poisoned data.
+    /// Files that can't be rebuilt are dropped.
+    pub fn recover(&mut self, url: Option<&Url>) {
+        let urls: Vec<Url> = self
+            .files
+            .iter()
+            .filter(|(file_url, file)| Some(*file_url) == url || file.is_poisoned())
+            .map(|(file_url, _)| file_url.clone())
+            .collect();
+
+        for url in urls {
+            info!("Rebuilding file: {}", url);
+            let content = self.files[&url].source_code.clone();
+
+            let result = utils::catch_panic(|| {
+                self.insert_file(url.clone(), &content, &mut HashSet::from([url.clone()]));
+                self.refresh_dependents(&url);
+            });
+            if let Err(message) = result {
+                error!("Couldn't rebuild {}: {}", url, message);
+                self.files.remove(&url);
+                self.imported_usages.remove(&url);
+            }
+        }
+    }
+
+    fn insert_file(&mut self, url: Url, content: &str, visiting: &mut HashSet<Url>) {
+        let includes = self.load_includes(&url, content, &[], visiting);
+        let imports = self.get_imports(&includes);
+        let tree = self.parser.parse(content, None);
+
+        self.stale_files.remove(&url);
+        self.files.insert(
+            url.clone(),
+            File::new(url.clone(), content, &tree, includes, &imports),
+        );
+        self.index_imported_usages(&url);
+    }
+
+    /// Resolves the include directives of a file, loading the included files
+    /// from disk when they are not already part of the workspace. Directives
+    /// already resolved in `previous` to a file still known keep their file.
+    fn load_includes(
+        &mut self,
+        url: &Url,
+        content: &str,
+        previous: &[Include],
+        visiting: &mut HashSet<Url>,
+    ) -> Vec<Include> {
+        let mut includes = include::find_includes(content);
+        let include_paths = self.settings.include_paths.clone();
+        let bundled_dir = self.bundled_include_dir.clone();
+
+        for include in includes.iter_mut() {
+            include.resolved = previous
+                .iter()
+                .find(|p| p.path == include.path && p.kind == include.kind)
+                .and_then(|p| p.resolved.clone())
+                .filter(|resolved| self.files.contains_key(resolved))
+                .or_else(|| include::resolve(include, url, &include_paths, bundled_dir.as_deref()));
+
+            if let Some(included_url) = &include.resolved {
+                if !self.files.contains_key(included_url) && visiting.insert(included_url.clone()) {
+                    self.load_file_from_disk(included_url.clone(), visiting);
+                }
+            }
+        }
+
+        includes
+    }
+
+    fn load_file_from_disk(&mut self, url: Url, visiting: &mut HashSet<Url>) {
+        let Ok(path) = url.to_file_path() else {
+            return;
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                info!("Indexing file from disk: {}", url);
+                self.insert_file(url, &content, visiting);
+            }
+            Err(e) => warn!("Couldn't read included file {}: {}", path.display(), e),
+        }
+    }
+
+    fn get_imports(&self, includes: &[Include]) -> Imports {
+        let mut imports = Imports::default();
+
+        for include in includes {
+            let Some(file) = include
+                .resolved
+                .as_ref()
+                .and_then(|url| self.files.get(url))
+            else {
+                imports.unresolved = true;
+                continue;
+            };
+            let st_manager = file.symbol_table_manager.lock().unwrap();
+
+            imports
+                .tables
+                .push((file.uri.clone(), st_manager.get_symbol_table()));
+        }
+
+        imports
+    }
+
+    /// Rebuilds the symbol table of a file, returning whether the symbols the
+    /// files including it can see changed.
+    fn rebuild_symbol_table(&mut self, url: &Url) -> bool {
+        let Some((content, previous, exports)) = self.files.get(url).map(|file| {
+            (
+                file.source_code.clone(),
+                file.includes.clone(),
+                file.get_exports_hash(),
+            )
+        }) else {
+            return false;
+        };
+
+        let includes =
+            self.load_includes(url, &content, &previous, &mut HashSet::from([url.clone()]));
+        let imports = self.get_imports(&includes);
+
+        let Some(file) = self.files.get_mut(url) else {
+            return false;
+        };
+        file.update_symbol_table(includes, &imports);
+        let changed = file.get_exports_hash() != exports;
+        self.stale_files.remove(url);
+        self.index_imported_usages(url);
+
+        changed
+    }
+
+    /// Rebuilds the symbol tables of every file that includes, directly or not, the
+    /// given file. The files including a dependent are only rebuilt when what the
+    /// dependent exports changed. Returns the rebuilt files.
+    fn refresh_dependents(&mut self, url: &Url) -> Vec<Url> {
+        let mut rebuilt = vec![];
+        let mut visited = HashSet::from([url.clone()]);
+        let mut queue = VecDeque::from([url.clone()]);
+        self.changed_exports.remove(url);
+
+        while let Some(current) = queue.pop_front() {
+            let dependents: Vec<Url> = self
+                .files
+                .iter()
+                .filter(|(_, file)| {
+                    file.includes
+                        .iter()
+                        .any(|include| include.resolved.as_ref() == Some(&current))
+                })
+                .map(|(dependent_url, _)| dependent_url.clone())
+                .collect();
+
+            for dependent in dependents {
+                if !visited.insert(dependent.clone()) {
+                    continue;
+                }
+                if self.rebuild_symbol_table(&dependent) {
+                    queue.push_back(dependent.clone());
+                }
+                rebuilt.push(dependent);
+            }
+        }
+
+        rebuilt
     }
 
     pub fn get_definition_location(&self, url: Url, symbol_position: Position) -> Option<Location> {
@@ -51,15 +458,79 @@ impl Workspace {
         symbol_position: Position,
         new_name: String,
     ) -> Option<WorkspaceEdit> {
-        let file = self.files.get_mut(&url).unwrap();
+        let definition = self.get_definition_location(url, symbol_position)?;
+        if self.is_bundled(&definition.uri) {
+            return None;
+        }
+
+        let usages = self.get_usages(&definition);
+
+        Some(rename::build_changes(definition, usages, new_name))
+    }
+
+    pub fn get_references(
+        &self,
+        url: Url,
+        symbol_position: Position,
+        include_declaration: bool,
+    ) -> Option<Vec<Location>> {
+        let definition = self.get_definition_location(url, symbol_position)?;
+        let usages = self.get_usages(&definition);
+
+        Some(references::build_locations(
+            definition,
+            usages,
+            include_declaration,
+        ))
+    }
 
-        file.rename_symbol(symbol_position, new_name)
+    /// Collects the usages of the symbol defined at `definition` in every file of the workspace.
+    fn get_usages(&self, definition: &Location) -> HashMap<Url, Vec<Range>> {
+        self.files
+            .iter()
+            .map(|(uri, file)| (uri.clone(), file.get_symbol_usages(definition)))
+            .filter(|(_, ranges)| !ranges.is_empty())
+            .collect()
+    }
+
+    pub fn get_semantic_tokens(&self, url: Url) -> Option<SemanticTokens> {
+        let file = self.files.get(&url)?;
+
+        file.get_semantic_tokens(&self.get_library_files())
+    }
+
+    pub fn get_semantic_tokens_delta(
+        &self,
+        url: Url,
+        previous_result_id: &str,
+    ) -> Option<SemanticTokensFullDeltaResult> {
+        let file = self.files.get(&url)?;
+
+        file.get_semantic_tokens_delta(previous_result_id, &self.get_library_files())
     }
 
-    pub fn get_semantic_tokens(&self, url: Url) -> Option<SemanticTokensResult> {
+    pub fn get_semantic_tokens_range(&self, url: Url, range: Range) -> Option<SemanticTokens> {
         let file = self.files.get(&url)?;
 
-        file.get_semantic_tokens()
+        file.get_semantic_tokens_range(range, &self.get_library_files())
+    }
+
+    pub fn get_document_symbols(&self, url: Url) -> Option<Vec<DocumentSymbol>> {
+        let file = self.files.get(&url)?;
+
+        file.get_document_symbols()
+    }
+
+    /// Searches the symbols of the workspace files, the bundled library files
+    /// aren't part of the workspace.
+    pub fn get_workspace_symbols(&self, query: &str) -> Vec<SymbolInformation> {
+        let symbols = self
+            .files
+            .values()
+            .filter(|file| !self.is_bundled(&file.uri))
+            .flat_map(|file| file.get_workspace_symbols());
+
+        workspace_symbols::search(query, symbols)
     }
 
     pub fn get_completion(
@@ -79,28 +550,253 @@ impl Workspace {
         file.get_hover_info(position)
     }
 
-    pub fn get_quick_diagnostics(&self, url: Url) -> Vec<Diagnostic> {
-        let maybe_file = self.files.get(&url);
+    pub fn get_signature_help(&self, url: Url, position: Position) -> Option<SignatureHelp> {
+        let file = self.files.get(&url)?;
 
-        if let Some(file) = maybe_file {
-            file.get_quick_diagnostics()
-        } else {
-            vec![]
-        }
+        file.get_signature_help(position)
     }
 
-    pub fn get_full_diagnostics(&self, url: Url) -> Vec<Diagnostic> {
-        let maybe_file = self.files.get(&url);
+    /// Prepares the computation of the diagnostics of a file on a snapshot of
+    /// it, so it can run without holding the workspace.
+    pub fn get_diagnostics_task(
+        &self,
+        url: &Url,
+    ) -> Option<impl FnOnce() -> Vec<Diagnostic> + Send + 'static> {
+        let file = self.files.get(url)?.snapshot();
+        let settings = self.settings.clone();
+        // A file only knows about its own usages, declarations used by the
+        // files including it aren't unused.
+        let external_usages = self.get_external_usages(url);
 
-        if let Some(file) = maybe_file {
-            file.get_full_diagnostics()
-        } else {
-            vec![]
+        Some(move || {
+            file.get_full_diagnostics(&settings)
+                .into_iter()
+                .filter(|diagnostic| {
+                    diagnostic.code
+                        != Some(NumberOrString::String(diagnostics::UNUSED_CODE.to_string()))
+                        || !external_usages.contains(&diagnostic.range)
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the definition ranges of the symbols of a file that are used by other files.
+    fn get_external_usages(&self, url: &Url) -> Vec<Range> {
+        self.imported_usages
+            .iter()
+            .filter(|(importer, _)| *importer != url)
+            .filter_map(|(_, usages)| usages.get(url))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Records which imported symbols a file uses, once its symbol table is built.
+    fn index_imported_usages(&mut self, url: &Url) {
+        let Some(file) = self.files.get(url) else {
+            return;
+        };
+
+        let mut usages: HashMap<Url, Vec<Range>> = HashMap::new();
+        for symbol in file
+            .symbol_table_manager
+            .lock()
+            .unwrap()
+            .get_imported_symbols()
+            .iter()
+            .filter(|symbol| !symbol.get_usages().is_empty())
+        {
+            usages
+                .entry(symbol.get_definition_location().uri.clone())
+                .or_default()
+                .push(symbol.get_definition_range());
         }
+
+        self.imported_usages.insert(url.clone(), usages);
     }
 
-    pub fn update_settings(&mut self, settings: Value) {
+    /// Returns the files whose symbol tables were rebuilt.
+    pub fn update_settings(&mut self, settings: Value) -> Vec<Url> {
         self.settings = Settings::parse(settings);
         info!("Settings: {:?}", self.settings);
+
+        // The include paths may have changed, every include is resolved again
+        for file in self.files.values_mut() {
+            for include in file.includes.iter_mut() {
+                include.resolved = None;
+            }
+        }
+
+        let urls: Vec<Url> = self.files.keys().cloned().collect();
+        for url in urls.iter() {
+            if self.rebuild_symbol_table(url) {
+                self.refresh_dependents(url);
+            }
+        }
+
+        urls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Location, NumberOrString, Position, Range, TextEdit, Url};
+
+    use super::Workspace;
+    use crate::features::diagnostics;
+    use crate::file::tests::new_parser;
+    use crate::include::tests::TempDir;
+    use crate::line_index::LineIndex;
+
+    const CORE: &str = "const bit<8> CORE_CONST = 2;
+";
+
+    const LIB: &str = "const bit<8> LIB_CONST = 1;
+const bit<8> LIB_TWICE = LIB_CONST + LIB_CONST;
+";
+
+    const MAIN: &str = "#include <core.p4>
+#include \"lib.p4\"
+
+control C(inout bit<8> x) {
+    apply {
+        x = LIB_CONST;
+        x = CORE_CONST;
+    }
+}
+";
+
+    /// A workspace with `main.p4` open, including `lib.p4` from its directory
+    /// and the bundled `core.p4`.
+    fn open_main() -> (Workspace, TempDir, TempDir) {
+        new_parser();
+        let project = TempDir::new("test-workspace", &[("main.p4", MAIN), ("lib.p4", LIB)]);
+        let bundled = TempDir::new("test-workspace-bundled", &[("core.p4", CORE)]);
+
+        let mut workspace = Workspace::new(tree_sitter_p4::language(), &[("core.p4", CORE)]);
+        workspace.bundled_include_dir = Some(bundled.path().to_path_buf());
+        workspace.add_file(project.url("main.p4"), MAIN, 1);
+
+        (workspace, project, bundled)
+    }
+
+    fn position(source: &str, text: &str) -> Position {
+        LineIndex::new(source).position(source, source.find(text).unwrap())
+    }
+
+    /// Ranges of every occurrence of `name` in a source.
+    fn occurrences(source: &str, name: &str) -> Vec<Range> {
+        let line_index = LineIndex::new(source);
+        source
+            .match_indices(name)
+            .map(|(start, _)| {
+                Range::new(
+                    line_index.position(source, start),
+                    line_index.position(source, start + name.len()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_goto_and_rename_across_files() {
+        let (mut workspace, project, bundled) = open_main();
+        let (main, lib) = (project.url("main.p4"), project.url("lib.p4"));
+
+        assert_eq!(
+            workspace.get_definition_location(main.clone(), position(MAIN, "LIB_CONST")),
+            Some(Location::new(lib.clone(), occurrences(LIB, "LIB_CONST")[0]))
+        );
+        assert_eq!(
+            workspace.get_definition_location(main.clone(), position(MAIN, "CORE_CONST")),
+            Some(Location::new(
+                bundled.url("core.p4"),
+                occurrences(CORE, "CORE_CONST")[0]
+            ))
+        );
+
+        let mut changes = workspace
+            .rename_symbol(
+                main.clone(),
+                position(MAIN, "LIB_CONST"),
+                "RENAMED".to_string(),
+            )
+            .and_then(|edit| edit.changes)
+            .unwrap();
+        let mut ranges = |url| -> Vec<Range> {
+            let mut edits: Vec<TextEdit> = changes.remove(url).unwrap_or_default();
+            assert!(edits.iter().all(|edit| edit.new_text == "RENAMED"));
+            edits.sort_by_key(|edit| edit.range.start);
+            edits.into_iter().map(|edit| edit.range).collect()
+        };
+        assert_eq!(ranges(&lib), occurrences(LIB, "LIB_CONST"));
+        assert_eq!(ranges(&main), occurrences(MAIN, "LIB_CONST"));
+        assert!(changes.is_empty());
+
+        // The bundled files are rewritten at each start, renaming in them would be lost
+        assert_eq!(
+            workspace.rename_symbol(main, position(MAIN, "CORE_CONST"), "RENAMED".to_string()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_references_across_files() {
+        let (workspace, project, _bundled) = open_main();
+        let (main, lib) = (project.url("main.p4"), project.url("lib.p4"));
+        let locations = |url: &Url, ranges: &[Range]| -> Vec<Location> {
+            ranges
+                .iter()
+                .map(|range| Location::new(url.clone(), *range))
+                .collect()
+        };
+        let lib_ranges = occurrences(LIB, "LIB_CONST");
+        let usages = [
+            locations(&lib, &lib_ranges[1..]),
+            locations(&main, &occurrences(MAIN, "LIB_CONST")),
+        ]
+        .concat();
+
+        assert_eq!(
+            workspace.get_references(main.clone(), position(MAIN, "LIB_CONST"), false),
+            Some(usages.clone())
+        );
+
+        // The declaration comes first, before the usages
+        let mut with_declaration = locations(&lib, &lib_ranges[..1]);
+        with_declaration.extend(usages);
+        assert_eq!(
+            workspace.get_references(main, position(MAIN, "LIB_CONST"), true),
+            Some(with_declaration)
+        );
+    }
+
+    /// Messages of the unused hints of a file.
+    fn unused_messages(workspace: &Workspace, url: &Url) -> Vec<String> {
+        let code = Some(NumberOrString::String(diagnostics::UNUSED_CODE.to_string()));
+        workspace.get_diagnostics_task(url).unwrap()()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.code == code)
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_unused_across_files() {
+        let (mut workspace, project, bundled) = open_main();
+        let (main, core) = (project.url("main.p4"), bundled.url("core.p4"));
+
+        assert_eq!(
+            unused_messages(&workspace, &project.url("lib.p4")),
+            vec!["`LIB_TWICE` is never used."]
+        );
+        assert!(unused_messages(&workspace, &core).is_empty());
+
+        // Once the only file using it is gone, the constant of `core.p4` is unused
+        workspace.close_file(main);
+        assert_eq!(
+            unused_messages(&workspace, &core),
+            vec!["`CORE_CONST` is never used."]
+        );
     }
 }
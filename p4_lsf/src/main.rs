@@ -1,6 +1,12 @@
 #[tokio::main]
 async fn main() {
     let language_def = include_str!("../language_def/rules.ron");
+    let bundled_includes = &[
+        ("core.p4", include_str!("../p4include/core.p4")),
+        ("v1model.p4", include_str!("../p4include/v1model.p4")),
+        ("psa.p4", include_str!("../p4include/psa.p4")),
+        ("pna.p4", include_str!("../p4include/pna.p4")),
+    ];
 
-    ls_framework::start_server(language_def, tree_sitter_p4::language()).await;
+    ls_framework::start_server(language_def, tree_sitter_p4::language(), bundled_includes).await;
 }